@@ -26,6 +26,21 @@ impl Config for ConfigRandomized {
     }
 }
 
+#[derive(Debug, Default)]
+pub struct ConfigFourKeys;
+impl Config for ConfigFourKeys {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![
+            (*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into(),
+            (*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into(),
+            (*b"0123456789abcdefghijklmnopqrstuv").into(),
+            (*b"YiVbn5Rk0cf4ZDhwm0p3qFh8vTjEJNxa").into(),
+        ]
+    }
+}
+
 fn encrypted_message(c: &mut Criterion) {
     // 32-byte payload.
     let payload = black_box(Alphanumeric.sample_string(&mut rand::thread_rng(), 32));
@@ -42,6 +57,32 @@ fn encrypted_message(c: &mut Criterion) {
         let encrypted = EncryptedMessage::<_, ConfigRandomized>::encrypt(payload.clone()).unwrap();
         b.iter(|| encrypted.decrypt().unwrap())
     });
+
+    c.bench_function("Decrypt 32-byte payload into scratch buffer", |b| {
+        let encrypted = EncryptedMessage::<_, ConfigRandomized>::encrypt(payload.clone()).unwrap();
+        let mut scratch = Vec::new();
+        b.iter(|| encrypted.decrypt_into(&mut scratch).unwrap())
+    });
+
+    // Diesel reads messages from the raw JSON sent by the backend, which is parsed directly into the message.
+    c.bench_function("Parse JSON representation", |b| {
+        let json = EncryptedMessage::<_, ConfigRandomized>::encrypt(payload.clone()).unwrap().to_string();
+        b.iter(|| serde_json::from_slice::<EncryptedMessage<String, ConfigRandomized>>(json.as_bytes()).unwrap())
+    });
+
+    c.bench_function("Parse JSON representation through serde_json::Value", |b| {
+        let json = EncryptedMessage::<_, ConfigRandomized>::encrypt(payload.clone()).unwrap().to_string();
+        b.iter(|| {
+            let value: serde_json::Value = serde_json::from_slice(json.as_bytes()).unwrap();
+            serde_json::from_value::<EncryptedMessage<String, ConfigRandomized>>(value).unwrap()
+        })
+    });
+
+    // The payload is only base64-decoded once, so trying more keys only adds the cost of verifying the tag with each key.
+    c.bench_function("Decrypt 32-byte payload with the last of 4 keys", |b| {
+        let encrypted = EncryptedMessage::<_, ConfigFourKeys>::encrypt_using_key(payload.clone(), 3).unwrap();
+        b.iter(|| encrypted.decrypt().unwrap())
+    });
 }
 
 criterion_group!(benches, encrypted_message);
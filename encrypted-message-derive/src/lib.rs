@@ -0,0 +1,153 @@
+//! Derive macros for the [`encrypted-message`](https://docs.rs/encrypted-message) crate.
+//!
+//! Enable the `derive` feature of `encrypted-message` to use them, instead of depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Meta, PathArguments, Type, parse_macro_input};
+
+/// Generates `encrypt_<field>` & `decrypt_<field>` methods for each field marked with `#[encrypted]`.
+///
+/// Marked fields must be of type `EncryptedMessage<P, C>` or `Option<EncryptedMessage<P, C>>`.
+/// The configuration `C` determines the keys & the strategy. Derive macros can't change the fields
+/// of the struct they're derived for, so the fields must be declared with these types, instead of the payload type.
+///
+/// By default, the configuration is `C::default()`. Configurations without a `Default` implementation
+/// can be provided with an expression, e.g. `#[encrypted(config = self.config)]`, which is borrowed by the generated methods.
+///
+/// - `encrypt_<field>(&mut self, payload: P)` encrypts the payload & stores it in the field.
+/// - `decrypt_<field>(&self)` decrypts the field, returning `Option<P>` for optional fields.
+#[proc_macro_derive(EncryptedFields, attributes(encrypted))]
+pub fn derive_encrypted_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "`EncryptedFields` can only be derived for structs."));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(&input.ident, "`EncryptedFields` can only be derived for structs with named fields."));
+    };
+
+    let mut methods = Vec::new();
+    for field in &fields.named {
+        let Some(attribute) = field.attrs.iter().find(|attribute| attribute.path().is_ident("encrypted")) else {
+            continue;
+        };
+        let config = config(attribute)?;
+
+        let ident = field.ident.as_ref().unwrap();
+        let encrypt = format_ident!("encrypt_{}", ident);
+        let decrypt = format_ident!("decrypt_{}", ident);
+        let encrypt_doc = format!("Encrypts the payload, & stores it in the `{ident}` field.");
+        let decrypt_doc = format!("Decrypts the payload of the `{ident}` field.");
+
+        let (message_type, payload_type, optional) = message_type(&field.ty)?;
+        let (encrypt_message, decrypt_message) = match &config {
+            Some(config) => (
+                quote!(<#message_type>::encrypt_with_config(payload, &(#config))?),
+                quote!(message.decrypt_with_config(&(#config))),
+            ),
+            None => (quote!(<#message_type>::encrypt(payload)?), quote!(message.decrypt())),
+        };
+
+        methods.push(if optional {
+            quote! {
+                #[doc = #encrypt_doc]
+                pub fn #encrypt(&mut self, payload: #payload_type) -> ::core::result::Result<(), ::encrypted_message::EncryptionError> {
+                    self.#ident = ::core::option::Option::Some(#encrypt_message);
+
+                    ::core::result::Result::Ok(())
+                }
+
+                #[doc = #decrypt_doc]
+                /// Returns `None` if the field isn't set.
+                pub fn #decrypt(&self) -> ::core::result::Result<::core::option::Option<#payload_type>, ::encrypted_message::DecryptionError> {
+                    self.#ident.as_ref().map(|message| #decrypt_message).transpose()
+                }
+            }
+        } else {
+            quote! {
+                #[doc = #encrypt_doc]
+                pub fn #encrypt(&mut self, payload: #payload_type) -> ::core::result::Result<(), ::encrypted_message::EncryptionError> {
+                    self.#ident = #encrypt_message;
+
+                    ::core::result::Result::Ok(())
+                }
+
+                #[doc = #decrypt_doc]
+                pub fn #decrypt(&self) -> ::core::result::Result<#payload_type, ::encrypted_message::DecryptionError> {
+                    let message = &self.#ident;
+                    #decrypt_message
+                }
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #ident #type_generics #where_clause {
+            #(#methods)*
+        }
+    })
+}
+
+/// Returns the configuration expression of an `#[encrypted]` attribute, if any.
+///
+/// Only the `config` argument is supported, since the strategy is determined by the configuration.
+fn config(attribute: &Attribute) -> syn::Result<Option<Expr>> {
+    if let Meta::Path(_) = attribute.meta {
+        return Ok(None);
+    }
+
+    let mut config = None;
+    attribute.parse_nested_meta(|meta| {
+        if meta.path.is_ident("config") {
+            config = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `#[encrypted]` argument, only `config` is supported."))
+        }
+    })?;
+
+    Ok(config)
+}
+
+/// Returns the message type & the payload type of an `EncryptedMessage<P, C>` or `Option<EncryptedMessage<P, C>>` field,
+/// & whether the field is optional.
+fn message_type(field_type: &Type) -> syn::Result<(&Type, &Type, bool)> {
+    let error = || Error::new_spanned(field_type, "`#[encrypted]` fields must be `EncryptedMessage<P, C>` or `Option<EncryptedMessage<P, C>>`.");
+
+    let (ident, argument) = last_segment(field_type).ok_or_else(error)?;
+    match ident.as_str() {
+        "EncryptedMessage" => Ok((field_type, argument, false)),
+        "Option" => match last_segment(argument) {
+            Some((ident, payload_type)) if ident == "EncryptedMessage" => Ok((argument, payload_type, true)),
+            _ => Err(error()),
+        },
+        _ => Err(error()),
+    }
+}
+
+/// Returns the name of the last segment of a type path, along with its first type argument.
+fn last_segment(field_type: &Type) -> Option<(String, &Type)> {
+    let Type::Path(path) = field_type else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+
+    match arguments.args.first()? {
+        GenericArgument::Type(argument) => Some((segment.ident.to_string(), argument)),
+        _ => None,
+    }
+}
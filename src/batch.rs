@@ -0,0 +1,266 @@
+//! Utilities to operate on many [`EncryptedMessage`]s at once.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use zeroize::Zeroizing;
+
+#[cfg(feature = "std")]
+use crate::error::StreamError;
+use crate::{EncryptedMessage, DecryptionError, DecryptionSettings, KeyCipher, ciphers_for, config::Config};
+
+/// Re-encrypts each [`EncryptedMessage`] with the encryption key of the provided [`Config`], see [`Config::encrypt_key`].
+///
+/// Messages that are already encrypted with the encryption key are passed through untouched,
+/// avoiding wasted work. Messages encrypted with an older key are decrypted & re-encrypted.
+///
+/// The returned iterator is lazy, messages are only rotated as they're consumed, so memory usage
+/// stays flat regardless of how many messages are processed. This allows streaming rows from a
+/// database cursor through it.
+///
+/// # Errors
+///
+/// Each item is an error if the message couldn't be decrypted. See [`EncryptedMessage::decrypt_with_config`] for more information.
+pub fn rotate<'a, P, C, I>(messages: I, config: &'a C) -> impl Iterator<Item = Result<EncryptedMessage<P, C>, DecryptionError>> + 'a
+where
+    P: 'a,
+    C: Config + 'a,
+    I: IntoIterator<Item = EncryptedMessage<P, C>>,
+    I::IntoIter: 'a,
+{
+    messages.into_iter().map(|message| message.rotate_with_config(config))
+}
+
+/// Verifies that each [`EncryptedMessage`] can be decrypted & authenticated with any of the keys of the provided [`Config`],
+/// returning a result for each message, in order.
+///
+/// This is useful to find corrupted messages at scale, e.g. in a maintenance job scanning a whole table.
+/// The keys are only retrieved once for all messages. With the `rayon` feature enabled, messages are verified in parallel.
+///
+/// # Errors
+///
+/// Each result is an error if the message couldn't be verified. See [`EncryptedMessage::verify_with_config`] for more information.
+pub fn verify_all<P, C>(messages: &[EncryptedMessage<P, C>], config: &C) -> Vec<Result<(), DecryptionError>>
+where
+    C: Config,
+{
+    let ciphers = ciphers_for(&config.decrypt_keys());
+    let settings = DecryptionSettings::from_config(config);
+    let verify = |message: &EncryptedMessage<P, C>| {
+        let mut buffer = Zeroizing::new(Vec::new());
+        message.decrypt_in_place_with_ciphers(&ciphers, settings, &mut buffer).map(|_| ())
+    };
+
+    #[cfg(feature = "rayon")]
+    let messages = messages.par_iter();
+    #[cfg(not(feature = "rayon"))]
+    let messages = messages.iter();
+
+    let results: Vec<_> = messages.map(verify).collect();
+    // The hook is called sequentially, as configurations aren't required to be `Sync`.
+    for result in &results {
+        config.on_decrypt(result.is_ok());
+    }
+
+    results
+}
+
+/// Decrypts newline-delimited JSON, where each line is the JSON representation of an [`EncryptedMessage`],
+/// returning a lazy iterator with the payload of each line, e.g. to process an export or a log file.
+///
+/// The keys are only retrieved once, & their ciphers are reused for all messages. Blank lines are skipped.
+///
+/// # Errors
+///
+/// Each item is an error if the line couldn't be read, parsed as a message, or decrypted, see [`StreamError`].
+/// Errors include the number of the line, starting at 1, & the following lines are still processed.
+#[cfg(feature = "std")]
+pub fn decrypt_stream_json<'a, P, C, R>(reader: R, config: &'a C) -> impl Iterator<Item = Result<P, StreamError>> + 'a
+where
+    P: DeserializeOwned + 'a,
+    C: Config + 'a,
+    R: BufRead + 'a,
+{
+    let ciphers = ciphers_for(&config.decrypt_keys());
+    let settings = DecryptionSettings::from_config(config);
+    let mut buffer = Zeroizing::new(Vec::new());
+
+    reader.lines().enumerate().filter_map(move |(index, line)| {
+        let line_number = index + 1;
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => return None,
+            Ok(line) => line,
+            Err(source) => return Some(Err(StreamError::Io { line: line_number, source })),
+        };
+
+        let message: EncryptedMessage<P, C> = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(source) => return Some(Err(StreamError::Parse { line: line_number, source })),
+        };
+
+        let result = message.decrypt_in_place_with_ciphers(&ciphers, settings, &mut buffer);
+        config.on_decrypt(result.is_ok());
+
+        Some(
+            result
+                .and_then(|_| message.headers.serialization.deserialize(&buffer))
+                .map_err(|source| StreamError::Decryption { line: line_number, source }),
+        )
+    })
+}
+
+/// Extends collections of [`EncryptedMessage`]s, e.g. a column loaded with Diesel, with a lazy decrypting iterator.
+///
+/// ```ignore
+/// use encrypted_message::DecryptIter as _;
+///
+/// let diaries = users.select(diary).load::<EncryptedMessage<String, UserConfig>>(conn)?.decrypt_all(&config);
+/// ```
+pub trait DecryptIter<P, C: Config>: IntoIterator<Item = EncryptedMessage<P, C>> + Sized {
+    /// Returns an iterator decrypting each [`EncryptedMessage`] like [`EncryptedMessage::decrypt_with_config`], as it's consumed.
+    ///
+    /// The keys are only retrieved once, & their ciphers are reused for all messages.
+    ///
+    /// # Errors
+    ///
+    /// Each item is an error if the message couldn't be decrypted. See [`EncryptedMessage::decrypt_with_config`] for more information.
+    fn decrypt_all(self, config: &C) -> DecryptAll<'_, Self::IntoIter, P, C> {
+        DecryptAll {
+            messages: self.into_iter(),
+            config,
+            ciphers: ciphers_for(&config.decrypt_keys()),
+            settings: DecryptionSettings::from_config(config),
+            buffer: Zeroizing::new(Vec::new()),
+            payload_type: PhantomData,
+        }
+    }
+}
+
+impl<P, C: Config, I: IntoIterator<Item = EncryptedMessage<P, C>>> DecryptIter<P, C> for I {}
+
+/// The iterator returned by [`DecryptIter::decrypt_all`].
+pub struct DecryptAll<'a, I, P, C> {
+    messages: I,
+    config: &'a C,
+    ciphers: Vec<KeyCipher>,
+    settings: DecryptionSettings,
+    buffer: Zeroizing<Vec<u8>>,
+    payload_type: PhantomData<fn() -> P>,
+}
+
+impl<P, C, I> Iterator for DecryptAll<'_, I, P, C>
+where
+    P: DeserializeOwned,
+    C: Config,
+    I: Iterator<Item = EncryptedMessage<P, C>>,
+{
+    type Item = Result<P, DecryptionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message = self.messages.next()?;
+
+        let result = message.decrypt_in_place_with_ciphers(&self.ciphers, self.settings, &mut self.buffer);
+        self.config.on_decrypt(result.is_ok());
+
+        Some(result.and_then(|_| message.headers.serialization.deserialize(&self.buffer)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.messages.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TestConfigDeterministic;
+
+    #[test]
+    fn rotates_to_primary_key() {
+        // Created using TestConfig's second key.
+        let message: EncryptedMessage<String, TestConfigDeterministic> = serde_json::from_value(serde_json::json!({
+            "p": "LC4u257NQw==",
+            "h": {
+                "iv": "nv6rH50Sn2Po320KT57fg1a3Lyu/IGeG",
+                "at": "/jK8Y7fOyA+S7/dTxRR3SQ==",
+            },
+        })).unwrap();
+        let current = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+
+        let rotated = rotate(vec![message], &TestConfigDeterministic).collect::<Result<Vec<_>, _>>().unwrap();
+
+        // The Deterministic strategy produces the same message for the same payload & key.
+        assert_eq!(rotated, vec![current]);
+    }
+
+    #[test]
+    fn passes_through_current_messages() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        let payload = message.payload.clone();
+
+        let rotated = rotate([message], &TestConfigDeterministic).next().unwrap().unwrap();
+        assert_eq!(rotated.payload, payload);
+    }
+
+    #[test]
+    fn returns_decryption_errors() {
+        let mut message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        message.payload = "invalid".to_string();
+
+        assert!(matches!(rotate([message], &TestConfigDeterministic).next().unwrap().unwrap_err(), DecryptionError::Base64Decoding(_)));
+    }
+
+    #[test]
+    fn decrypts_all_messages() {
+        let mut tampered = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        tampered.payload = crate::utilities::base64::encode(b"tampered");
+        let messages = vec![
+            EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap(),
+            tampered,
+            EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap(),
+        ];
+
+        let mut payloads = messages.decrypt_all(&TestConfigDeterministic);
+        assert_eq!(payloads.size_hint(), (3, Some(3)));
+        assert_eq!(payloads.next().unwrap().unwrap(), "hi :)");
+        assert!(matches!(payloads.next().unwrap(), Err(DecryptionError::Decryption)));
+        assert_eq!(payloads.next().unwrap().unwrap(), "rigo does pretty codes");
+        assert!(payloads.next().is_none());
+    }
+
+    #[test]
+    fn decrypts_stream_json() {
+        let other_key = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_key("hi :)".to_string(), &(*b"0123456789abcdefghijklmnopqrstuv").into()).unwrap();
+        let ndjson = [
+            EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap().to_string(),
+            "not a message".to_string(),
+            String::new(),
+            other_key.to_string(),
+            EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap().to_string(),
+        ].join("\n");
+
+        let mut payloads = decrypt_stream_json::<String, _, _>(ndjson.as_bytes(), &TestConfigDeterministic);
+        assert_eq!(payloads.next().unwrap().unwrap(), "hi :)");
+        assert!(matches!(payloads.next().unwrap(), Err(StreamError::Parse { line: 2, .. })));
+        assert!(matches!(payloads.next().unwrap(), Err(StreamError::Decryption { line: 4, source: DecryptionError::Decryption })));
+        assert_eq!(payloads.next().unwrap().unwrap(), "rigo does pretty codes");
+        assert!(payloads.next().is_none());
+    }
+
+    #[test]
+    fn verifies_all_messages() {
+        let valid = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        let mut tampered = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        tampered.payload = crate::utilities::base64::encode(b"tampered");
+
+        let results = verify_all(&[valid, tampered], &TestConfigDeterministic);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(DecryptionError::Decryption)));
+    }
+}
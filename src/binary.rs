@@ -0,0 +1,180 @@
+//! A compact binary representation of [`EncryptedMessage`], using the [`bincode`] crate.
+
+use alloc::{collections::BTreeMap, string::String};
+use core::{marker::PhantomData, num::NonZeroUsize};
+
+use bincode::Options as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    EncryptedMessage,
+    EncryptedMessageHeaders,
+    MAX_BLOCK_SIZE,
+    config::Config,
+    error::BinaryError,
+    serialization::Serialization,
+    strategy::StrategyKind,
+    utilities::base64,
+};
+
+/// The binary representation of an [`EncryptedMessage`].
+///
+/// Unlike the JSON representation, the fields are stored as raw bytes, & the payload is length-prefixed
+/// using a variable-length integer.
+#[derive(Deserialize, Serialize)]
+struct BinaryMessage {
+    version: u8,
+    nonce: [u8; 24],
+    tag: Option<[u8; 16]>,
+    serialization: u8,
+    strategy: Option<StrategyKind>,
+    timestamp: Option<i64>,
+    metadata: BTreeMap<String, String>,
+    padding: Option<NonZeroUsize>,
+    key_commitment: Option<[u8; 32]>,
+    hmac_tag: Option<[u8; 32]>,
+    recipient_keys: Vec<Vec<u8>>,
+    payload: Vec<u8>,
+}
+
+/// Returns the bincode options used for the binary representation.
+fn options() -> impl bincode::Options {
+    bincode::DefaultOptions::new().reject_trailing_bytes()
+}
+
+impl<P, C: Config> EncryptedMessage<P, C> {
+    /// Returns the compact binary representation of the message.
+    ///
+    /// The binary representation is independent of the JSON representation, & is considerably smaller,
+    /// as the fields aren't base64-encoded & no field names are stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message's fields are not valid, e.g. if it was parsed from malformed JSON.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinaryError> {
+        let message = BinaryMessage {
+            version: self.headers.version,
+            nonce: base64::decode(&self.headers.nonce)?.try_into().map_err(|_| BinaryError::InvalidLength)?,
+            tag: match &self.headers.tag {
+                Some(tag) => Some(base64::decode(tag)?.try_into().map_err(|_| BinaryError::InvalidLength)?),
+                None => None,
+            },
+            serialization: self.headers.serialization.id(),
+            strategy: self.headers.strategy,
+            timestamp: self.headers.timestamp,
+            metadata: self.headers.metadata.clone(),
+            padding: self.headers.padding,
+            key_commitment: match &self.headers.key_commitment {
+                Some(key_commitment) => Some(base64::decode(key_commitment)?.try_into().map_err(|_| BinaryError::InvalidLength)?),
+                None => None,
+            },
+            hmac_tag: match &self.headers.hmac_tag {
+                Some(hmac_tag) => Some(base64::decode(hmac_tag)?.try_into().map_err(|_| BinaryError::InvalidLength)?),
+                None => None,
+            },
+            recipient_keys: self.headers.recipient_keys.iter().map(base64::decode).collect::<Result<_, _>>()?,
+            payload: base64::decode(&self.payload)?,
+        };
+
+        Ok(options().serialize(&message)?)
+    }
+
+    /// Parses a message from the binary representation returned by [`EncryptedMessage::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a valid binary representation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let message: BinaryMessage = options().deserialize(bytes)?;
+        if message.padding.is_some_and(|block_size| block_size.get() > MAX_BLOCK_SIZE) {
+            return Err(BinaryError::InvalidLength);
+        }
+
+        Ok(EncryptedMessage {
+            payload: base64::encode(message.payload),
+            headers: EncryptedMessageHeaders {
+                version: message.version,
+                nonce: base64::encode(message.nonce),
+                tag: message.tag.map(base64::encode),
+                serialization: Serialization::from_id(message.serialization).ok_or(BinaryError::UnsupportedSerialization(message.serialization))?,
+                strategy: message.strategy,
+                timestamp: message.timestamp,
+                metadata: message.metadata,
+                padding: message.padding,
+                key_commitment: message.key_commitment.map(base64::encode),
+                hmac_tag: message.hmac_tag.map(base64::encode),
+                recipient_keys: message.recipient_keys.into_iter().map(base64::encode).collect(),
+            },
+            payload_type: PhantomData,
+            config: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{TestConfigDeterministic, TestConfigPadded};
+
+    #[test]
+    fn to_and_from_bytes() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+
+        let bytes = message.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 1 + 24 + 1 + 16 + 1 + 2 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 24);
+        assert!(bytes.len() < message.to_string().len());
+
+        let parsed = EncryptedMessage::<String, TestConfigDeterministic>::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.decrypt().unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn recipient_keys_to_and_from_bytes() {
+        let keys = TestConfigDeterministic.keys();
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_for_recipients("rigo does pretty codes".to_string(), &keys).unwrap();
+
+        let parsed = EncryptedMessage::<String, TestConfigDeterministic>::from_bytes(&message.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.decrypt().unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let bytes = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap().to_bytes().unwrap();
+
+        // Truncated.
+        assert!(matches!(EncryptedMessage::<String, TestConfigDeterministic>::from_bytes(&bytes[..30]).unwrap_err(), BinaryError::Bincode(_)));
+
+        // Trailing bytes.
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(EncryptedMessage::<String, TestConfigDeterministic>::from_bytes(&trailing).unwrap_err(), BinaryError::Bincode(_)));
+
+        // Unsupported serialization format.
+        let mut unsupported = bytes;
+        unsupported[42] = u8::MAX;
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigDeterministic>::from_bytes(&unsupported).unwrap_err(),
+            BinaryError::UnsupportedSerialization(_),
+        ));
+    }
+
+    #[test]
+    fn test_invalid_nonce_length() {
+        let mut message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        message.headers.nonce = base64::encode([0; 12]);
+
+        assert!(matches!(message.to_bytes().unwrap_err(), BinaryError::InvalidLength));
+    }
+
+    #[test]
+    fn test_invalid_block_size() {
+        let mut message = EncryptedMessage::<String, TestConfigPadded>::encrypt("hi :)".to_string()).unwrap();
+        message.headers.padding = NonZeroUsize::new(MAX_BLOCK_SIZE + 1);
+
+        let bytes = message.to_bytes().unwrap();
+        assert!(matches!(EncryptedMessage::<String, TestConfigPadded>::from_bytes(&bytes).unwrap_err(), BinaryError::InvalidLength));
+    }
+}
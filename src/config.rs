@@ -1,8 +1,16 @@
 //! Contains the [`Config`] trait used to define the configuration for an [`EncryptedMessage`](crate::EncryptedMessage).
 
-use std::fmt::Debug;
+use alloc::{string::String, vec::Vec};
+use core::{fmt::{Debug, Write as _}, num::NonZeroUsize};
+#[cfg(feature = "std")]
+use core::time::Duration;
 
-pub use secrecy::{Secret, ExposeSecret};
+pub use secrecy::{Secret, SecretString, ExposeSecret};
+
+use hkdf::Hkdf;
+use sha2::{Digest as _, Sha256};
+
+use crate::{error::ConfigError, serialization::Serialization, strategy::Strategy as _};
 
 /// A trait to define the configuration for an [`EncryptedMessage`](crate::EncryptedMessage).
 /// This allows you to effectively define different keys for different kinds of data if needed.
@@ -14,14 +22,393 @@ pub trait Config: Debug {
     /// The first key is considered the primary key, & is always used for encryption.
     /// The next keys are used in the order provided when the primary key can't decrypt
     /// an [`EncryptedMessage`](crate::EncryptedMessage). This allows for key rotation.
+    ///
+    /// The keys are zeroized when they're dropped. Keys derived in this method, e.g. with PBKDF2, should be converted
+    /// with [`key_generation::into_secret`](crate::key_generation::into_secret), so the derived arrays are zeroized too.
     fn keys(&self) -> Vec<Secret<[u8; 32]>>;
 
     /// Returns the primary key, which is the first key in [`Config::keys`].
     fn primary_key(&self) -> Secret<[u8; 32]> {
-        let mut keys = self.keys();
-        assert!(!keys.is_empty(), "Must provide at least one key.");
+        // The key is copied, instead of moved out of the vector, so all its copies are zeroized when the vector is dropped.
+        let keys = self.keys();
+        let primary_key = keys.first().expect("Must provide at least one key.");
+
+        Secret::new(*primary_key.expose_secret())
+    }
+
+    /// Returns the key used to encrypt new messages.
+    ///
+    /// Defaults to the primary key, see [`Config::primary_key`]. Together with [`Config::decrypt_keys`], this allows
+    /// separating duties, e.g. giving read replicas a configuration that can decrypt messages, but not produce new ones
+    /// with the current key. Rotated messages are re-encrypted with this key.
+    fn encrypt_key(&self) -> Secret<[u8; 32]> {
+        self.primary_key()
+    }
+
+    /// Returns the keys used to decrypt messages, tried in order.
+    ///
+    /// Defaults to all the keys, see [`Config::keys`]. This may include keys that are no longer used for encryption,
+    /// so messages encrypted with them can still be decrypted until they're rotated.
+    fn decrypt_keys(&self) -> Vec<Secret<[u8; 32]>> {
+        self.keys()
+    }
+
+    /// Returns the key used to compute blind indexes, see [`EncryptedMessage::blind_index_with_config`](crate::EncryptedMessage::blind_index_with_config).
+    ///
+    /// Defaults to a key derived from the primary key using HKDF-SHA256, so it's never used directly for encryption.
+    /// Since blind indexes are stored next to encrypted messages, changing this key (e.g. by rotating the primary key
+    /// with the default implementation) requires recomputing them. Override this method to use a dedicated key instead.
+    fn index_key(&self) -> Secret<[u8; 32]> {
+        let primary_key = self.primary_key();
+
+        let mut index_key = [0; 32];
+        Hkdf::<Sha256>::new(None, primary_key.expose_secret()).expand(b"encrypted-message blind index", &mut index_key).unwrap();
+
+        crate::key_generation::into_secret(&mut index_key)
+    }
+
+    /// Returns the context passed to the [`Strategy`](crate::strategy::Strategy) when generating nonces.
+    ///
+    /// Defaults to an empty context. With the [`Deterministic`](crate::strategy::Deterministic) strategy,
+    /// providing a different context per column (e.g. `b"users.email"`) ensures identical payloads
+    /// produce different encrypted messages across columns, even when they share a key.
+    ///
+    /// Changing the context of an existing [`Deterministic`](crate::strategy::Deterministic) configuration
+    /// changes the encrypted messages it produces, so existing messages won't match new ones anymore.
+    fn context(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Returns the format used to serialize payloads before encryption.
+    ///
+    /// Defaults to [`Serialization::Json`]. The format is stored in the headers of each
+    /// [`EncryptedMessage`](crate::EncryptedMessage), so changing it doesn't affect existing messages.
+    fn serialization(&self) -> Serialization {
+        Serialization::Json
+    }
+
+    /// Returns whether the auth tag is appended to the encrypted payload, instead of stored in the headers.
+    ///
+    /// Defaults to `false`. The combined form (`ciphertext || tag`) matches the default output of many AEAD libraries,
+    /// which eases exchanging messages with other systems. Both forms can always be decrypted.
+    fn combined_tag(&self) -> bool {
+        false
+    }
+
+    /// Returns whether an HMAC-SHA256 tag of the encrypted payload is stored in the headers of new messages,
+    /// in addition to its auth tag.
+    ///
+    /// Defaults to `false`. The Poly1305 auth tag is 128 bits long, which is sufficient. This is for compliance regimes
+    /// requiring 256-bit tags: the 32-byte tag is computed with a key derived from the encryption key, over the nonce,
+    /// the metadata, the encrypted payload & its auth tag, & is verified before decryption. It's stored in the `ht` header,
+    /// which is required while this returns `true`, so messages encrypted without it can't be decrypted by the configuration.
+    /// **It makes encryption & decryption slower, & messages larger.**
+    fn hmac_tag(&self) -> bool {
+        false
+    }
+
+    /// Returns the version of the message format written by encryption methods.
+    ///
+    /// Defaults to `0`, the initial format. Version `1` derives [`Deterministic`](crate::strategy::Deterministic) nonces
+    /// with HKDF-SHA256 & a domain-separation label. Version `2` also stores a commitment to the key, so a message can't be
+    /// crafted to decrypt under several keys. New configurations should return [`FORMAT_VERSION`](crate::FORMAT_VERSION),
+    /// the latest version. Messages of older versions are decrypted down to [`Config::min_format_version`], but changing
+    /// the version of a [`Deterministic`](crate::strategy::Deterministic) configuration changes the messages it produces,
+    /// so existing messages won't match new ones until they're re-encrypted. Encryption returns a
+    /// [`ConfigError::UnsupportedVersion`] error for versions after [`FORMAT_VERSION`](crate::FORMAT_VERSION).
+    fn format_version(&self) -> u8 {
+        0
+    }
+
+    /// Returns the oldest version of the message format accepted by decryption methods.
+    ///
+    /// Defaults to `2` when [`Config::format_version`] is `2` or later, & to `0` otherwise. Messages without a key commitment
+    /// are then rejected, so the `v` & `kc` headers can't be stripped to downgrade a message to a version without one.
+    /// Configurations moving to version `2` with existing messages of older versions should return `0`
+    /// until those messages are re-encrypted, e.g. with [`rotate`](crate::rotate).
+    fn min_format_version(&self) -> u8 {
+        if self.format_version() >= crate::KEY_COMMITMENT_VERSION {
+            crate::KEY_COMMITMENT_VERSION
+        } else {
+            0
+        }
+    }
+
+    /// Returns the block size that serialized payloads are padded to before encryption, if any.
+    ///
+    /// Defaults to `None`. The ciphertext length reveals the length of the payload, which leaks information for short
+    /// enumerable values, like PINs. With padding, only the number of blocks is revealed. The padding is a `0x80` byte
+    /// followed by zeros (ISO/IEC 7816-4), inside the authenticated plaintext, & the block size is stored in the headers.
+    ///
+    /// **Padding increases the size of each message by up to the block size**, so it should be kept small for large payloads.
+    /// Block sizes are limited to 65536 bytes, & encryption returns a [`ConfigError::UnsupportedBlockSize`] error with larger ones.
+    fn pad_to(&self) -> Option<NonZeroUsize> {
+        None
+    }
+
+    /// Returns the maximum length of serialized payloads, in bytes, before padding.
+    ///
+    /// Defaults to `None`, i.e. unlimited. Encrypting a longer payload returns an
+    /// [`EncryptionError::PayloadTooLarge`](crate::error::EncryptionError::PayloadTooLarge) error, before it's padded, encrypted & encoded,
+    /// which bounds the memory used by encryption & the size of messages, e.g. for services encrypting user-provided input.
+    /// The payload is still serialized before its length is known, so the input itself should be bounded upstream too.
+    fn max_plaintext_len(&self) -> Option<usize> {
+        None
+    }
 
-        keys.remove(0)
+    /// Returns whether empty payloads are rejected, e.g. empty strings, byte strings, sequences & maps.
+    ///
+    /// Defaults to `false`. When enabled, encrypting an empty payload returns an
+    /// [`EncryptionError::EmptyPayload`](crate::error::EncryptionError::EmptyPayload) error, for applications where
+    /// empty values are a bug, e.g. to keep them from being stored next to `NULL`s in a nullable column.
+    /// `None` isn't considered empty, as it's serialized as `null`.
+    fn reject_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the current epoch, e.g. the current month, which new messages are scoped to.
+    ///
+    /// Defaults to `None`. With the [`Deterministic`](crate::strategy::Deterministic) strategy, the same payload
+    /// then only produces the same encrypted message within an epoch, which keeps messages queryable within an epoch,
+    /// but limits frequency analysis to the messages of an epoch. The epoch is stored in the metadata of each message,
+    /// under the [`EPOCH_METADATA_KEY`](crate::EPOCH_METADATA_KEY) key, see [`EncryptedMessage::epoch`](crate::EncryptedMessage::epoch),
+    /// & isn't needed for decryption. Querying messages of a past epoch requires encrypting the payload in that epoch,
+    /// see [`EncryptedMessage::encrypt_in_epoch_with_config`](crate::EncryptedMessage::encrypt_in_epoch_with_config).
+    fn epoch(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns whether the time of encryption is stored in the headers of new messages,
+    /// which can be retrieved with [`EncryptedMessage::created_at`](crate::EncryptedMessage::created_at).
+    ///
+    /// Defaults to `false`. This is useful for auditing & expiration policies.
+    #[cfg(feature = "std")]
+    fn stamp_time(&self) -> bool {
+        false
+    }
+
+    /// Returns how long new messages can be decrypted for, e.g. for password reset tokens.
+    ///
+    /// Defaults to `None`, so messages never expire. The expiration time is stored in the metadata of each message,
+    /// under the [`EXPIRES_AT_METADATA_KEY`](crate::EXPIRES_AT_METADATA_KEY) key, so it's authenticated, & decrypting
+    /// the message after that time returns a [`DecryptionError::Expired`](crate::error::DecryptionError::Expired) error.
+    /// Changing the validity doesn't affect existing messages. **Expiration relies on the system clock**, & isn't
+    /// enforced when decrypting without the `std` feature. As [`Deterministic`](crate::strategy::Deterministic) nonces
+    /// depend on the metadata, messages encrypted at different times differ, so they can't be queried by equality.
+    #[cfg(feature = "std")]
+    fn validity(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called once per decryption attempt using the configuration, with whether the payload was authenticated & decrypted,
+    /// e.g. to emit audit events when sensitive fields are accessed.
+    ///
+    /// Defaults to doing nothing. It's called before the payload is deserialized, so a payload that fails to deserialize
+    /// still counts as a successful decryption. Methods that don't take a configuration, like
+    /// [`EncryptedMessage::decrypt_with_key`](crate::EncryptedMessage::decrypt_with_key), don't call it.
+    fn on_decrypt(&self, success: bool) {
+        let _ = success;
+    }
+
+    /// Returns the lowercase name of the strategy of the configuration, e.g. `randomized`, to use in logs & metrics.
+    ///
+    /// The strategy stored messages were actually encrypted with is returned by
+    /// [`EncryptedMessage::encoded_strategy`](crate::EncryptedMessage::encoded_strategy), which is displayed by the same name.
+    fn strategy_name(&self) -> &'static str {
+        Self::Strategy::NAME
+    }
+
+    /// Returns a hex-encoded fingerprint of the keys, which can be logged & compared across nodes
+    /// to check that they all loaded the same keys, in the same order.
+    ///
+    /// It's a SHA-256 hash of a fingerprint of each key, derived from the key using HKDF-SHA256,
+    /// so it doesn't reveal the keys.
+    fn keys_fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for key in self.keys() {
+            let mut key_fingerprint = [0; 32];
+            Hkdf::<Sha256>::new(None, key.expose_secret()).expand(b"encrypted-message key fingerprint", &mut key_fingerprint).unwrap();
+            hasher.update(key_fingerprint);
+        }
+
+        let mut fingerprint = String::with_capacity(64);
+        for byte in hasher.finalize() {
+            write!(fingerprint, "{byte:02x}").unwrap();
+        }
+
+        fingerprint
+    }
+
+    /// Checks that this configuration doesn't share any keys with a configuration using a different encryption strategy.
+    ///
+    /// Sharing keys between [`Deterministic`](crate::strategy::Deterministic) & [`Randomized`](crate::strategy::Randomized)
+    /// configurations weakens the guarantees of both, so it's recommended to run this check in your tests:
+    ///
+    /// ```
+    /// # use encrypted_message::{config::{Config, Secret}, strategy::{Deterministic, Randomized}};
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct DeterministicConfig;
+    /// # impl Config for DeterministicConfig {
+    /// #     type Strategy = Deterministic;
+    /// #
+    /// #     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+    /// #         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    /// #     }
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct RandomizedConfig;
+    /// # impl Config for RandomizedConfig {
+    /// #     type Strategy = Randomized;
+    /// #
+    /// #     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+    /// #         vec![(*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into()]
+    /// #     }
+    /// # }
+    /// #
+    /// #[test]
+    /// fn configs_dont_share_keys() {
+    ///     DeterministicConfig.assert_key_separation(&RandomizedConfig).unwrap();
+    /// }
+    /// # DeterministicConfig.assert_key_separation(&RandomizedConfig).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConfigError::SharedKey`] error if a key of this configuration is used by the other configuration,
+    /// & both configurations use different encryption strategies.
+    fn assert_key_separation<O: Config>(&self, other: &O) -> Result<(), ConfigError>
+    where
+        Self: Sized,
+    {
+        if Self::Strategy::KIND == O::Strategy::KIND {
+            return Ok(());
+        }
+
+        let other_keys = other.keys();
+        for (index, key) in self.keys().iter().enumerate() {
+            if other_keys.iter().any(|other_key| other_key.expose_secret() == key.expose_secret()) {
+                return Err(ConfigError::SharedKey { index });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Wraps a [`Config`], computing its keys once & reusing them for every operation.
+///
+/// This is useful when [`Config::keys`] is expensive, e.g. when it derives keys with PBKDF2,
+/// & the same configuration is used for many operations. The keys are zeroized when it's dropped.
+///
+/// The encryption, decryption & index keys are also computed when it's created, see [`Config::encrypt_key`], [`Config::decrypt_keys`] & [`Config::index_key`].
+///
+/// Since the configuration is part of the [`EncryptedMessage`](crate::EncryptedMessage) type,
+/// messages must be typed with the `CachedConfig` to use it, e.g. `EncryptedMessage<String, CachedConfig<MyConfig>>`.
+#[derive(Debug)]
+pub struct CachedConfig<C: Config> {
+    config: C,
+    keys: Vec<Secret<[u8; 32]>>,
+    encrypt_key: Secret<[u8; 32]>,
+    decrypt_keys: Vec<Secret<[u8; 32]>>,
+    index_key: Secret<[u8; 32]>,
+}
+
+impl<C: Config> CachedConfig<C> {
+    /// Wraps the provided configuration, computing its keys.
+    pub fn new(config: C) -> Self {
+        let keys = config.keys();
+        let encrypt_key = config.encrypt_key();
+        let decrypt_keys = config.decrypt_keys();
+        let index_key = config.index_key();
+
+        Self { config, keys, encrypt_key, decrypt_keys, index_key }
+    }
+
+    /// Returns the wrapped configuration.
+    pub fn into_inner(self) -> C {
+        self.config
+    }
+}
+
+impl<C: Config> Config for CachedConfig<C> {
+    type Strategy = C::Strategy;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        self.keys.iter().map(|key| Secret::new(*key.expose_secret())).collect()
+    }
+
+    fn primary_key(&self) -> Secret<[u8; 32]> {
+        let key = self.keys.first().expect("Must provide at least one key.");
+
+        Secret::new(*key.expose_secret())
+    }
+
+    fn encrypt_key(&self) -> Secret<[u8; 32]> {
+        Secret::new(*self.encrypt_key.expose_secret())
+    }
+
+    fn decrypt_keys(&self) -> Vec<Secret<[u8; 32]>> {
+        self.decrypt_keys.iter().map(|key| Secret::new(*key.expose_secret())).collect()
+    }
+
+    fn index_key(&self) -> Secret<[u8; 32]> {
+        Secret::new(*self.index_key.expose_secret())
+    }
+
+    fn context(&self) -> &[u8] {
+        self.config.context()
+    }
+
+    fn serialization(&self) -> Serialization {
+        self.config.serialization()
+    }
+
+    fn combined_tag(&self) -> bool {
+        self.config.combined_tag()
+    }
+
+    fn hmac_tag(&self) -> bool {
+        self.config.hmac_tag()
+    }
+
+    fn format_version(&self) -> u8 {
+        self.config.format_version()
+    }
+
+    fn min_format_version(&self) -> u8 {
+        self.config.min_format_version()
+    }
+
+    fn pad_to(&self) -> Option<NonZeroUsize> {
+        self.config.pad_to()
+    }
+
+    fn max_plaintext_len(&self) -> Option<usize> {
+        self.config.max_plaintext_len()
+    }
+
+    fn reject_empty(&self) -> bool {
+        self.config.reject_empty()
+    }
+
+    fn epoch(&self) -> Option<String> {
+        self.config.epoch()
+    }
+
+    #[cfg(feature = "std")]
+    fn stamp_time(&self) -> bool {
+        self.config.stamp_time()
+    }
+
+    #[cfg(feature = "std")]
+    fn validity(&self) -> Option<Duration> {
+        self.config.validity()
+    }
+
+    fn on_decrypt(&self, success: bool) {
+        self.config.on_decrypt(success);
     }
 }
 
@@ -29,6 +416,8 @@ pub trait Config: Debug {
 mod tests {
     use super::*;
 
+    use core::cell::Cell;
+
     use crate::testing::TestConfigRandomized as TestConfig;
 
     #[test]
@@ -36,4 +425,157 @@ mod tests {
         let config = TestConfig;
         assert_eq!(config.primary_key().expose_secret(), config.keys()[0].expose_secret());
     }
+
+    #[test]
+    fn encrypt_and_decrypt_keys_default_to_keys() {
+        let config = TestConfig;
+        assert_eq!(config.encrypt_key().expose_secret(), config.primary_key().expose_secret());
+        assert_eq!(config.decrypt_keys().len(), config.keys().len());
+    }
+
+    #[test]
+    fn index_key_is_derived_from_primary_key() {
+        let config = TestConfig;
+
+        let mut index_key = [0; 32];
+        Hkdf::<Sha256>::new(None, config.primary_key().expose_secret()).expand(b"encrypted-message blind index", &mut index_key).unwrap();
+        assert_eq!(config.index_key().expose_secret(), &index_key);
+        assert_ne!(config.index_key().expose_secret(), config.primary_key().expose_secret());
+    }
+
+    #[test]
+    fn context_defaults_to_empty() {
+        assert!(TestConfig.context().is_empty());
+    }
+
+    #[test]
+    fn serialization_defaults_to_json() {
+        assert_eq!(TestConfig.serialization(), Serialization::Json);
+    }
+
+    #[test]
+    fn combined_tag_defaults_to_false() {
+        assert!(!TestConfig.combined_tag());
+    }
+
+    #[test]
+    fn format_version_defaults_to_initial() {
+        assert_eq!(TestConfig.format_version(), 0);
+    }
+
+    #[test]
+    fn min_format_version_requires_key_commitment() {
+        assert_eq!(TestConfig.min_format_version(), 0);
+        assert_eq!(crate::testing::TestConfigLatestFormat.min_format_version(), crate::KEY_COMMITMENT_VERSION);
+    }
+
+    #[test]
+    fn pad_to_defaults_to_none() {
+        assert_eq!(TestConfig.pad_to(), None);
+    }
+
+    #[test]
+    fn max_plaintext_len_defaults_to_none() {
+        assert_eq!(TestConfig.max_plaintext_len(), None);
+    }
+
+    #[test]
+    fn hmac_tag_defaults_to_false() {
+        assert!(!TestConfig.hmac_tag());
+    }
+
+    #[test]
+    fn reject_empty_defaults_to_false() {
+        assert!(!TestConfig.reject_empty());
+    }
+
+    #[test]
+    fn epoch_defaults_to_none() {
+        assert_eq!(TestConfig.epoch(), None);
+    }
+
+    #[test]
+    fn stamp_time_defaults_to_false() {
+        assert!(!TestConfig.stamp_time());
+    }
+
+    #[test]
+    fn validity_defaults_to_none() {
+        assert_eq!(TestConfig.validity(), None);
+    }
+
+    #[test]
+    fn strategy_name() {
+        assert_eq!(TestConfig.strategy_name(), "randomized");
+        assert_eq!(crate::testing::TestConfigDeterministic.strategy_name(), "deterministic");
+    }
+
+    #[test]
+    fn keys_fingerprint() {
+        use crate::testing::{TestConfigCombinedTag, TestConfigDeterministic};
+
+        #[derive(Debug)]
+        struct ReversedConfig;
+        impl Config for ReversedConfig {
+            type Strategy = crate::strategy::Randomized;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                let mut keys = TestConfig.keys();
+                keys.reverse();
+
+                keys
+            }
+        }
+
+        let fingerprint = TestConfig.keys_fingerprint();
+        assert_eq!(fingerprint.len(), 64);
+
+        // Test that configurations with the same keys have the same fingerprint.
+        assert_eq!(TestConfigDeterministic.keys_fingerprint(), fingerprint);
+        assert_eq!(CachedConfig::new(TestConfig).keys_fingerprint(), fingerprint);
+
+        // Test that the fingerprint depends on the keys & their order.
+        assert_ne!(TestConfigCombinedTag.keys_fingerprint(), fingerprint);
+        assert_ne!(ReversedConfig.keys_fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn key_separation() {
+        use crate::testing::TestConfigDeterministic;
+
+        // The test configurations share their keys.
+        assert!(matches!(TestConfig.assert_key_separation(&TestConfigDeterministic).unwrap_err(), ConfigError::SharedKey { index: 0 }));
+
+        // Test that configurations using the same strategy may share keys.
+        assert!(TestConfig.assert_key_separation(&TestConfig).is_ok());
+    }
+
+    #[test]
+    fn cached_config_computes_keys_once() {
+        #[derive(Debug, Default)]
+        struct CountingConfig {
+            calls: Cell<usize>,
+        }
+
+        impl Config for CountingConfig {
+            type Strategy = <TestConfig as Config>::Strategy;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                self.calls.set(self.calls.get() + 1);
+                TestConfig.keys()
+            }
+        }
+
+        let config = CachedConfig::new(CountingConfig::default());
+        let calls = config.config.calls.get();
+        for _ in 0..3 {
+            assert_eq!(config.primary_key().expose_secret(), TestConfig.primary_key().expose_secret());
+            assert_eq!(config.encrypt_key().expose_secret(), TestConfig.primary_key().expose_secret());
+            assert_eq!(config.keys().len(), 2);
+            assert_eq!(config.decrypt_keys().len(), 2);
+            assert_eq!(config.index_key().expose_secret(), TestConfig.index_key().expose_secret());
+        }
+
+        assert_eq!(config.into_inner().calls.get(), calls);
+    }
 }
@@ -0,0 +1,213 @@
+//! Diagnostics to sanity-check how your data behaves with the encryption strategies & keys.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use chacha20poly1305::AeadInPlace as _;
+use secrecy::ExposeSecret as _;
+use zeroize::Zeroizing;
+
+use crate::{
+    EncryptedMessage,
+    EncryptionSettings,
+    KeyCipher,
+    NONCE_SIZE,
+    TAG_SIZE,
+    ciphers_for,
+    config::Config,
+    error::{ConfigError, DecryptionError},
+    message_associated_data,
+    pad,
+    strategy::{Deterministic, Strategy as _},
+    unwrap_key,
+    utilities::base64,
+};
+
+/// Returns the pairs of indexes of distinct payloads whose [`Deterministic`] nonces collide,
+/// using the encryption key & the nonce settings of the provided configuration.
+///
+/// The nonces are generated like the configuration generates them, i.e. with its format version, its padding
+/// & its context, scoped to the metadata it adds, like the epoch. The payloads must be serialized like
+/// the configuration serializes them, e.g. as JSON. Identical payloads always share a nonce by design, so they're not reported.
+///
+/// Nonces are 192-bit HMAC-SHA256 or HKDF-SHA256 outputs, so a collision is never expected in practice.
+/// This allows checking it against a sample of your actual data, e.g. before deciding to migrate to another mode.
+///
+/// # Errors
+///
+/// Returns a [`ConfigError`] error if the configuration is invalid, as encryption would.
+pub fn collision_probe<C: Config<Strategy = Deterministic>>(payloads: &[&[u8]], config: &C) -> Result<Vec<(usize, usize)>, ConfigError> {
+    let settings = EncryptionSettings::from_config(config)?;
+    let key = config.encrypt_key();
+
+    let mut nonces = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for (index, payload) in payloads.iter().enumerate() {
+        let nonce = deterministic_nonce(&settings, key.expose_secret(), payload);
+        match nonces.get(&nonce) {
+            Some(&first) if payloads[first] != *payload => collisions.push((first, index)),
+            Some(_) => {},
+            None => {
+                nonces.insert(nonce, index);
+            },
+        }
+    }
+
+    Ok(collisions)
+}
+
+/// Returns the [`Deterministic`] nonce encryption generates for the serialized payload with the provided settings.
+fn deterministic_nonce(settings: &EncryptionSettings<'_>, key: &[u8; 32], payload: &[u8]) -> [u8; 24] {
+    let mut buffer = Zeroizing::new(payload.to_vec());
+    if let Some(block_size) = settings.padding {
+        pad(&mut buffer, block_size);
+    }
+
+    Deterministic::generate_nonce_for_version(settings.version, &buffer, key, &settings.nonce_context())
+}
+
+impl<P, C: Config> EncryptedMessage<P, C> {
+    /// Returns the indexes of all keys of [`Config::decrypt_keys`] the message is authenticated under,
+    /// e.g. to audit a table for key confusion or duplicate keys.
+    ///
+    /// Each key is tried, regardless of the key commitment & the HMAC tag, so in a correct setup, exactly one key is returned.
+    /// More than one key means the keys contain duplicates, or that the message was crafted to decrypt under several keys,
+    /// which the key commitment prevents from version 2 of the message format. For messages encrypted for recipients,
+    /// see [`EncryptedMessage::encrypt_for_recipients_with_config`], the keys of all recipients that are in the configuration are returned.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`DecryptionError::Base64Decoding`] error if a part of the message cannot be base64-decoded.
+    /// - Returns a [`DecryptionError::Decryption`] error if the nonce or the tag doesn't have the expected length.
+    pub fn keys_that_decrypt_with_config(&self, config: &C) -> Result<Vec<usize>, DecryptionError> {
+        let ciphers = ciphers_for(&config.decrypt_keys());
+
+        if !self.headers.recipient_keys.is_empty() {
+            let wrapped_keys = self.headers.recipient_keys.iter().map(base64::decode).collect::<Result<Vec<_>, _>>()?;
+
+            return Ok(matching_indexes(&ciphers, |KeyCipher { cipher, .. }| wrapped_keys.iter().any(|wrapped_key| unwrap_key(cipher, wrapped_key).is_some())));
+        }
+
+        let mut payload = Zeroizing::new(Vec::new());
+        base64::decode_into(&self.payload, &mut payload)?;
+        let nonce = base64::decode(&self.headers.nonce)?;
+        let tag = match &self.headers.tag {
+            Some(tag) => base64::decode(tag)?,
+            None => {
+                let payload_len = payload.len().checked_sub(TAG_SIZE).ok_or(DecryptionError::Decryption)?;
+                payload.split_off(payload_len)
+            },
+        };
+        if nonce.len() != NONCE_SIZE || tag.len() != TAG_SIZE {
+            return Err(DecryptionError::Decryption);
+        }
+
+        let key_commitment = self.headers.key_commitment.as_deref().map(base64::decode).transpose()?;
+        let associated_data = message_associated_data(self.headers.version, key_commitment.as_deref(), self.headers.serialization, self.headers.padding, &self.headers.metadata);
+
+        Ok(matching_indexes(&ciphers, |KeyCipher { cipher, .. }| {
+            // The payload is decrypted into a copy, so it can be decrypted again with the next key.
+            let mut buffer = payload.clone();
+            cipher.decrypt_in_place_detached(nonce.as_slice().into(), &associated_data, &mut buffer, tag.as_slice().into()).is_ok()
+        }))
+    }
+}
+
+impl<P, C: Config + Default> EncryptedMessage<P, C> {
+    /// This method is a shorthand for [`EncryptedMessage::keys_that_decrypt_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn keys_that_decrypt(&self) -> Result<Vec<usize>, DecryptionError> {
+        self.keys_that_decrypt_with_config(&C::default())
+    }
+}
+
+/// Returns the indexes of the ciphers matching the predicate.
+fn matching_indexes(ciphers: &[KeyCipher], predicate: impl Fn(&KeyCipher) -> bool) -> Vec<usize> {
+    ciphers.iter().enumerate().filter(|(_, cipher)| predicate(cipher)).map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{TestConfigDeterministic, TestConfigLatestFormat, TestConfigPadded};
+
+    #[test]
+    fn finds_no_collisions() {
+        let payloads: Vec<Vec<u8>> = (0..1000_u32).map(|number| serde_json::to_vec(&number).unwrap()).collect();
+        let mut payloads: Vec<&[u8]> = payloads.iter().map(Vec::as_slice).collect();
+
+        // Test that identical payloads aren't reported.
+        payloads.push(b"42");
+
+        assert!(collision_probe(&payloads, &TestConfigDeterministic).unwrap().is_empty());
+        assert!(collision_probe(&payloads, &TestConfigLatestFormat).unwrap().is_empty());
+        assert!(collision_probe(&payloads, &TestConfigPadded).unwrap().is_empty());
+    }
+
+    #[test]
+    fn probes_the_nonces_of_encryption() {
+        use alloc::string::{String, ToString as _};
+
+        #[derive(Debug, Default)]
+        struct EpochConfig;
+        impl Config for EpochConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<secrecy::Secret<[u8; 32]>> {
+                TestConfigPadded.keys()
+            }
+
+            fn pad_to(&self) -> Option<core::num::NonZeroUsize> {
+                TestConfigPadded.pad_to()
+            }
+
+            fn epoch(&self) -> Option<String> {
+                Some("2024-06".to_string())
+            }
+        }
+
+        // Test that the nonces are scoped to the configured metadata & padding, like the nonces of encrypted messages.
+        let message = EncryptedMessage::<String, EpochConfig>::encrypt("rigo".to_string()).unwrap();
+        let settings = EncryptionSettings::from_config(&EpochConfig).unwrap();
+        let nonce = deterministic_nonce(&settings, EpochConfig.encrypt_key().expose_secret(), br#""rigo""#);
+        assert_eq!(base64::encode(nonce), message.headers.nonce);
+    }
+
+    #[test]
+    fn keys_that_decrypt() {
+        use alloc::string::{String, ToString as _};
+
+        use secrecy::Secret;
+
+        use crate::testing::TestConfigRandomized;
+
+        #[derive(Debug, Default)]
+        struct DuplicateKeysConfig;
+        impl Config for DuplicateKeysConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                let mut keys = TestConfigDeterministic.keys();
+                keys.push(Secret::new(*TestConfigDeterministic.keys()[1].expose_secret()));
+
+                keys
+            }
+
+            fn format_version(&self) -> u8 {
+                crate::FORMAT_VERSION
+            }
+        }
+
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+        assert_eq!(message.keys_that_decrypt().unwrap(), [1]);
+
+        // Test that every key is tried, even with a key commitment.
+        let message = EncryptedMessage::<String, DuplicateKeysConfig>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+        assert!(message.headers.key_commitment.is_some());
+        assert_eq!(message.keys_that_decrypt().unwrap(), [1, 2]);
+
+        let recipient_keys = TestConfigRandomized.keys();
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt_for_recipients("rigo does pretty codes".to_string(), &recipient_keys[1..]).unwrap();
+        assert_eq!(message.keys_that_decrypt().unwrap(), [1]);
+    }
+}
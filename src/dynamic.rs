@@ -0,0 +1,306 @@
+//! Contains the [`DynConfig`] trait, an object-safe version of [`Config`] used to select configurations at runtime.
+//!
+//! [`DynConfig`] is implemented for all configurations, & has methods of the same names as [`Config`],
+//! so only one of the traits should be imported in a module, to avoid ambiguous method calls.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Debug, num::NonZeroUsize};
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+use chacha20poly1305::{KeyInit as _, XChaCha20Poly1305};
+use secrecy::{ExposeSecret as _, Secret};
+use serde::{Serialize, de::DeserializeOwned};
+use zeroize::Zeroizing;
+
+use crate::{
+    DecryptionSettings,
+    EncryptedMessage,
+    EncryptionSettings,
+    check_config,
+    ciphers_for,
+    config::Config,
+    error::{ConfigError, DecryptionError, EncryptionError},
+    serialization::Serialization,
+    strategy::{Strategy as _, StrategyKind},
+};
+
+/// An object-safe version of [`Config`], where the strategy is erased to a [`StrategyKind`].
+///
+/// This allows storing a `Box<dyn DynConfig>`, to select a configuration at runtime, e.g. from a feature flag,
+/// & using it with [`EncryptedMessage::encrypt_with_dyn_config`] & [`EncryptedMessage::decrypt_with_dyn_config`].
+/// It's implemented for all types implementing [`Config`], see its methods for their documentation.
+///
+/// ```
+/// use encrypted_message::{
+///     EncryptedMessage,
+///     config::{Config, Secret},
+///     dynamic::DynConfig,
+///     strategy::{Deterministic, Randomized},
+/// };
+///
+/// #[derive(Debug)]
+/// struct QueryableConfig;
+/// impl Config for QueryableConfig {
+///     type Strategy = Deterministic;
+///
+///     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+///         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+///     }
+/// }
+///
+/// #[derive(Debug)]
+/// struct PrivateConfig;
+/// impl Config for PrivateConfig {
+///     type Strategy = Randomized;
+///
+///     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+///         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+///     }
+/// }
+///
+/// let queryable = false;
+/// let config: Box<dyn DynConfig> = if queryable { Box::new(QueryableConfig) } else { Box::new(PrivateConfig) };
+///
+/// let message = EncryptedMessage::<String, PrivateConfig>::encrypt_with_dyn_config("rigo does pretty codes".to_string(), &*config).unwrap();
+/// assert_eq!(message.decrypt_with_dyn_config(&*config).unwrap(), "rigo does pretty codes");
+/// ```
+pub trait DynConfig: Debug {
+    /// Returns the kind of the [`Config::Strategy`].
+    fn strategy_kind(&self) -> StrategyKind;
+
+    /// See [`Config::keys`].
+    fn keys(&self) -> Vec<Secret<[u8; 32]>>;
+
+    /// See [`Config::encrypt_key`].
+    fn encrypt_key(&self) -> Secret<[u8; 32]>;
+
+    /// See [`Config::decrypt_keys`].
+    fn decrypt_keys(&self) -> Vec<Secret<[u8; 32]>>;
+
+    /// See [`Config::context`].
+    fn context(&self) -> &[u8];
+
+    /// See [`Config::serialization`].
+    fn serialization(&self) -> Serialization;
+
+    /// See [`Config::combined_tag`].
+    fn combined_tag(&self) -> bool;
+
+    /// See [`Config::hmac_tag`].
+    fn hmac_tag(&self) -> bool;
+
+    /// See [`Config::format_version`].
+    fn format_version(&self) -> u8;
+
+    /// See [`Config::min_format_version`].
+    fn min_format_version(&self) -> u8;
+
+    /// See [`Config::pad_to`].
+    fn pad_to(&self) -> Option<NonZeroUsize>;
+
+    /// See [`Config::max_plaintext_len`].
+    fn max_plaintext_len(&self) -> Option<usize>;
+
+    /// See [`Config::reject_empty`].
+    fn reject_empty(&self) -> bool;
+
+    /// See [`Config::epoch`].
+    fn epoch(&self) -> Option<String>;
+
+    /// See [`Config::stamp_time`].
+    #[cfg(feature = "std")]
+    fn stamp_time(&self) -> bool;
+
+    /// See [`Config::validity`].
+    #[cfg(feature = "std")]
+    fn validity(&self) -> Option<Duration>;
+
+    /// See [`Config::on_decrypt`].
+    fn on_decrypt(&self, success: bool);
+}
+
+impl<C: Config> DynConfig for C {
+    fn strategy_kind(&self) -> StrategyKind {
+        C::Strategy::KIND
+    }
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        Config::keys(self)
+    }
+
+    fn encrypt_key(&self) -> Secret<[u8; 32]> {
+        Config::encrypt_key(self)
+    }
+
+    fn decrypt_keys(&self) -> Vec<Secret<[u8; 32]>> {
+        Config::decrypt_keys(self)
+    }
+
+    fn context(&self) -> &[u8] {
+        Config::context(self)
+    }
+
+    fn serialization(&self) -> Serialization {
+        Config::serialization(self)
+    }
+
+    fn combined_tag(&self) -> bool {
+        Config::combined_tag(self)
+    }
+
+    fn hmac_tag(&self) -> bool {
+        Config::hmac_tag(self)
+    }
+
+    fn format_version(&self) -> u8 {
+        Config::format_version(self)
+    }
+
+    fn min_format_version(&self) -> u8 {
+        Config::min_format_version(self)
+    }
+
+    fn pad_to(&self) -> Option<NonZeroUsize> {
+        Config::pad_to(self)
+    }
+
+    fn max_plaintext_len(&self) -> Option<usize> {
+        Config::max_plaintext_len(self)
+    }
+
+    fn reject_empty(&self) -> bool {
+        Config::reject_empty(self)
+    }
+
+    fn epoch(&self) -> Option<String> {
+        Config::epoch(self)
+    }
+
+    #[cfg(feature = "std")]
+    fn stamp_time(&self) -> bool {
+        Config::stamp_time(self)
+    }
+
+    #[cfg(feature = "std")]
+    fn validity(&self) -> Option<Duration> {
+        Config::validity(self)
+    }
+
+    fn on_decrypt(&self, success: bool) {
+        Config::on_decrypt(self, success);
+    }
+}
+
+impl<P: Debug + DeserializeOwned + Serialize, C: Config> EncryptedMessage<P, C> {
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// but with a configuration selected at runtime.
+    ///
+    /// The nonce is generated with the strategy of the provided configuration, instead of the [`Config::Strategy`]
+    /// of the message type, & the [`Randomized`](crate::strategy::Randomized) strategy uses its default nonce source.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::encrypt_with_config`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the strategy is [`StrategyKind::Counter`] & the `std` feature is disabled.
+    pub fn encrypt_with_dyn_config(payload: P, config: &dyn DynConfig) -> Result<Self, EncryptionError> {
+        let settings = EncryptionSettings::from_dyn_config(config)?;
+        let payload = settings.serialize(&payload)?;
+
+        let key = config.encrypt_key();
+        let cipher = XChaCha20Poly1305::new(key.expose_secret().into());
+        let strategy = config.strategy_kind();
+
+        Ok(Self::encrypt_bytes_with_strategy(payload, &settings, &key, &cipher, strategy, |buffer| {
+            strategy.generate_nonce_for_version(settings.version, buffer, key.expose_secret(), &settings.nonce_context())
+        }))
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`] like [`EncryptedMessage::decrypt_with_config`],
+    /// but with a configuration selected at runtime.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_with_dyn_config(&self, config: &dyn DynConfig) -> Result<P, DecryptionError> {
+        let mut payload = Zeroizing::new(Vec::new());
+        let result = self.decrypt_in_place_with_ciphers(&ciphers_for(&config.decrypt_keys()), DecryptionSettings::from_dyn_config(config), &mut payload);
+        config.on_decrypt(result.is_ok());
+        result?;
+
+        self.headers.serialization.deserialize(&payload)
+    }
+}
+
+impl<'a> EncryptionSettings<'a> {
+    /// Returns the settings of the provided configuration, like [`EncryptionSettings::from_config`].
+    fn from_dyn_config(config: &'a dyn DynConfig) -> Result<Self, ConfigError> {
+        let version = config.format_version();
+        let padding = config.pad_to();
+        check_config(version, padding)?;
+
+        Ok(Self {
+            version,
+            serialization: config.serialization(),
+            combined_tag: config.combined_tag(),
+            hmac_tag: config.hmac_tag(),
+            context: config.context(),
+            scope: &[],
+            #[cfg(feature = "std")]
+            timestamp: config.stamp_time().then(crate::now),
+            #[cfg(not(feature = "std"))]
+            timestamp: None,
+            #[cfg(feature = "std")]
+            metadata: crate::config_metadata(config.epoch(), config.validity()),
+            #[cfg(not(feature = "std"))]
+            metadata: crate::config_metadata(config.epoch()),
+            padding,
+            max_len: config.max_plaintext_len(),
+            reject_empty: config.reject_empty(),
+        })
+    }
+}
+
+impl DecryptionSettings {
+    /// Returns the settings of the provided configuration, like [`DecryptionSettings::from_config`].
+    fn from_dyn_config(config: &dyn DynConfig) -> Self {
+        Self { min_version: config.min_format_version(), hmac_tag: config.hmac_tag() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::{boxed::Box, string::{String, ToString as _}};
+
+    use crate::testing::{TestConfigDeterministic, TestConfigPadded, TestConfigRandomized};
+
+    #[test]
+    fn matches_static_configs() {
+        // Test that messages are encrypted like with the static configuration.
+        let config: Box<dyn DynConfig> = Box::new(TestConfigDeterministic);
+        assert_eq!(config.strategy_kind(), StrategyKind::Deterministic);
+
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_dyn_config("rigo does pretty codes".to_string(), &*config).unwrap();
+        assert_eq!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+        assert_eq!(message.decrypt_with_dyn_config(&*config).unwrap(), "rigo does pretty codes");
+
+        let config: Box<dyn DynConfig> = Box::new(TestConfigPadded);
+        let message = EncryptedMessage::<String, TestConfigPadded>::encrypt_with_dyn_config("rigo does pretty codes".to_string(), &*config).unwrap();
+        assert_eq!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+    }
+
+    #[test]
+    fn uses_strategy_of_config() {
+        let configs: [Box<dyn DynConfig>; 2] = [Box::new(TestConfigDeterministic), Box::new(TestConfigRandomized)];
+        for config in configs {
+            let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_dyn_config("rigo does pretty codes".to_string(), &*config).unwrap();
+            assert_eq!(message.encoded_strategy(), Some(config.strategy_kind()));
+            assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        }
+    }
+}
@@ -1,27 +1,336 @@
 //! Error types for the encryption & decryption operations.
 
+use alloc::{boxed::Box, string::String};
+
 use thiserror::Error;
 
 /// Returned from [`EncryptedMessage`](crate::EncryptedMessage) encryption methods when an error occurs.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum EncryptionError {
-    /// This error occurs when a payload could not be serialized into JSON.
-    #[error("The payload could not be serialized into JSON.")]
-    Serialization(#[from] serde_json::Error),
+    /// This error occurs when a payload could not be serialized with the configured [`Serialization`](crate::serialization::Serialization).
+    #[error("The payload could not be serialized.")]
+    Serialization(#[source] Box<dyn core::error::Error + Send + Sync>),
+
+    /// This error occurs when the key index provided to [`EncryptedMessage::encrypt_with_config_using_key`](crate::EncryptedMessage::encrypt_with_config_using_key)
+    /// is out of range of the configured keys.
+    #[error("The key index {index} is out of range, only {len} keys are configured.")]
+    KeyIndexOutOfRange {
+        /// The provided key index.
+        index: usize,
+        /// The number of configured keys.
+        len: usize,
+    },
+
+    /// This error occurs when the serialized payload is longer than [`Config::max_plaintext_len`](crate::config::Config::max_plaintext_len).
+    #[error("The serialized payload is {len} bytes long, exceeding the maximum of {max} bytes.")]
+    PayloadTooLarge {
+        /// The length of the serialized payload.
+        len: usize,
+        /// The maximum length of serialized payloads.
+        max: usize,
+    },
+
+    /// This error occurs when the payload is empty, & [`Config::reject_empty`](crate::config::Config::reject_empty) returns `true`.
+    #[error("The payload is empty.")]
+    EmptyPayload,
+
+    /// This error occurs when no recipient keys are provided to
+    /// [`EncryptedMessage::encrypt_for_recipients_with_config`](crate::EncryptedMessage::encrypt_for_recipients_with_config).
+    #[error("No recipient keys were provided.")]
+    NoRecipients,
+
+    /// This error occurs when the configuration is invalid, e.g. when [`Config::pad_to`](crate::config::Config::pad_to)
+    /// returns a block size that's too large.
+    #[error(transparent)]
+    Config(#[from] ConfigError),
 }
 
 /// Returned from [`EncryptedMessage`](crate::EncryptedMessage) decryption methods when an error occurs.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum DecryptionError {
     /// This error occurs when a field in [`EncryptedMessage`](crate::EncryptedMessage) could not be base64-decoded.
-    #[error(transparent)]
-    Base64Decoding(#[from] base64::DecodeError),
+    #[error("{0}")]
+    Base64Decoding(base64::DecodeError),
+
+    /// This error occurs when the message was created with a version of the message format this version of the crate doesn't support.
+    #[error("The message format version {0} is not supported.")]
+    UnsupportedVersion(u8),
 
     /// This error occurs when a payload could not be decrypted with any of the available keys.
+    ///
+    /// XChaCha20-Poly1305 doesn't commit to the key it was encrypted with, so a failed authentication
+    /// looks exactly the same whether the message was encrypted with a key that isn't configured, or
+    /// was encrypted with a configured key & then tampered with. Both cases are reported as this error,
+    /// & it should be treated as possible tampering until a key rotation issue is ruled out.
+    ///
+    /// From version 2 of the message format, messages store a commitment to their key, & this error also occurs
+    /// when the commitment is missing, or doesn't match any of the available keys.
     #[error("The payload could not be decrypted with any of the available keys.")]
     Decryption,
 
+    /// This error occurs when the message has expired, see [`Config::validity`](crate::config::Config::validity).
+    /// The payload was authenticated, but isn't returned.
+    #[cfg(feature = "std")]
+    #[error("The message has expired.")]
+    Expired,
+
     /// This error occurs when a payload could not be deserialized into the expected type.
-    #[error("The payload could not be deserialized into the expected type.")]
-    Deserialization(#[from] serde_json::Error),
+    #[error("The payload could not be deserialized into `{type_name}`.")]
+    Deserialization {
+        /// The name of the type the payload was deserialized into.
+        type_name: &'static str,
+
+        /// The underlying deserialization error.
+        #[source]
+        source: Box<dyn core::error::Error + Send + Sync>,
+    },
+}
+
+/// Returned when the parts of an [`EncryptedMessage`](crate::EncryptedMessage) are invalid.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// This error occurs when a part of the message could not be base64-decoded.
+    #[error("The {field} could not be base64-decoded: {error}")]
+    Base64Decoding {
+        /// The name of the invalid part.
+        field: &'static str,
+
+        /// The underlying decoding error.
+        error: base64::DecodeError,
+    },
+
+    /// This error occurs when a part of the message doesn't have the expected length.
+    #[error("The {field} must be {expected} bytes long, but is {actual} bytes long.")]
+    InvalidLength {
+        /// The name of the invalid part.
+        field: &'static str,
+
+        /// The expected length, in bytes.
+        expected: usize,
+
+        /// The actual length, in bytes.
+        actual: usize,
+    },
+
+    /// This error occurs when the JSON representation of the message doesn't have the expected structure.
+    #[error("The message could not be parsed from JSON.")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Returned from [`decrypt_stream_json`](crate::decrypt_stream_json) for each line that couldn't be decrypted.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StreamError {
+    /// This error occurs when a line could not be read.
+    #[error("The line {line} could not be read.")]
+    Io {
+        /// The number of the line, starting at 1.
+        line: usize,
+
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// This error occurs when a line is not the JSON representation of a message.
+    #[error("The line {line} could not be parsed as a message.")]
+    Parse {
+        /// The number of the line, starting at 1.
+        line: usize,
+
+        /// The underlying parsing error.
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// This error occurs when the message of a line could not be decrypted.
+    #[error("The message on line {line} could not be decrypted.")]
+    Decryption {
+        /// The number of the line, starting at 1.
+        line: usize,
+
+        /// The underlying decryption error.
+        #[source]
+        source: DecryptionError,
+    },
+}
+
+/// Returned from [`Config`](crate::config::Config) checks, & from encryption methods, when a configuration is invalid.
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// This error occurs when configurations using different encryption strategies share a key.
+    #[error("The key at index {index} is shared with a configuration using a different encryption strategy.")]
+    SharedKey {
+        /// The index of the shared key in [`Config::keys`](crate::config::Config::keys).
+        index: usize,
+    },
+
+    /// This error occurs when [`Config::format_version`](crate::config::Config::format_version) returns a version
+    /// of the message format this version of the crate doesn't support.
+    #[error("The message format version {0} is not supported.")]
+    UnsupportedVersion(u8),
+
+    /// This error occurs when [`Config::pad_to`](crate::config::Config::pad_to) returns a block size larger than 65536 bytes.
+    #[error("The block size {0} is larger than the maximum of 65536 bytes.")]
+    UnsupportedBlockSize(usize),
+}
+
+/// Returned from [`Counter::seed`](crate::strategy::Counter::seed) when the state can't be seeded.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CounterError {
+    /// This error occurs when nonces were already generated, & the state would change the prefix or move the counter backwards,
+    /// which could reuse nonces.
+    #[error("The counter can't be moved backwards once it has been used.")]
+    Backwards,
+}
+
+/// Returned from [`key_generation::from_mnemonic`](crate::key_generation::from_mnemonic) when an error occurs.
+#[cfg(feature = "bip39")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MnemonicError {
+    /// This error occurs when the phrase isn't a valid BIP39 mnemonic, e.g. when a word is misspelled or the checksum doesn't match.
+    #[error("The phrase is not a valid BIP39 mnemonic.")]
+    Invalid(#[from] bip39::Error),
+}
+
+/// Returned from [`Keyring`](crate::keyring::Keyring) parsing when an error occurs.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum KeyringError {
+    /// This error occurs when the JSON representation of the keyring doesn't have the expected structure.
+    #[error("The keyring could not be parsed from JSON.")]
+    Json(#[from] serde_json::Error),
+
+    /// This error occurs when a key isn't a base64 or hex-encoded 32-byte key.
+    #[error("The key `{id}` is not a base64 or hex-encoded 32-byte key.")]
+    InvalidKey {
+        /// The id of the invalid key.
+        id: String,
+    },
+
+    /// This error occurs when the primary key isn't in the keyring.
+    #[error("The primary key `{id}` is not in the keyring.")]
+    MissingPrimaryKey {
+        /// The id of the primary key.
+        id: String,
+    },
+
+    /// This error occurs when an enabled key of a Tink keyset isn't a 32-byte XChaCha20Poly1305 or AES-GCM key.
+    #[cfg(feature = "tink")]
+    #[error("The Tink key `{id}` of type `{type_url}` is not a supported 32-byte AEAD key.")]
+    UnsupportedTinkKey {
+        /// The id of the unsupported key.
+        id: String,
+
+        /// The type URL of the unsupported key.
+        type_url: String,
+    },
+}
+
+impl DecryptionError {
+    /// Returns `true` if the payload couldn't be authenticated with any of the available keys,
+    /// e.g. because the key it was encrypted with isn't configured anymore.
+    ///
+    /// This can't be told apart from tampering, so such errors are also corruption errors, see [`DecryptionError::Decryption`].
+    pub fn is_key_related(&self) -> bool {
+        matches!(self, DecryptionError::Decryption)
+    }
+
+    /// Returns `true` if the message is malformed or was tampered with, e.g. because it was modified in storage.
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, DecryptionError::Base64Decoding(_) | DecryptionError::Decryption)
+    }
+}
+
+// Implemented manually, as `base64::DecodeError` only implements `Error` with the `std` feature.
+impl From<base64::DecodeError> for DecryptionError {
+    fn from(error: base64::DecodeError) -> Self {
+        DecryptionError::Base64Decoding(error)
+    }
+}
+
+/// Returned when converting an [`EncryptedMessage`](crate::EncryptedMessage) to or from its binary representation fails.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BinaryError {
+    /// This error occurs when a field in [`EncryptedMessage`](crate::EncryptedMessage) could not be base64-decoded.
+    #[error(transparent)]
+    Base64Decoding(#[from] base64::DecodeError),
+
+    /// This error occurs when the nonce or tag doesn't have the expected length, or the block size of the padding is too large.
+    #[error("The nonce, tag or block size has an invalid length.")]
+    InvalidLength,
+
+    /// This error occurs when the binary representation references an unsupported serialization format.
+    #[error("The serialization format {0} is not supported.")]
+    UnsupportedSerialization(u8),
+
+    /// This error occurs when the binary representation could not be encoded or decoded.
+    #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+}
+
+/// Allows encryption errors to flow through [`std::io::Result`], as [`std::io::ErrorKind::Other`] errors.
+#[cfg(feature = "std")]
+impl From<EncryptionError> for std::io::Error {
+    fn from(error: EncryptionError) -> Self {
+        std::io::Error::other(error)
+    }
+}
+
+/// Allows decryption errors to flow through [`std::io::Result`], as [`std::io::ErrorKind::InvalidData`] errors.
+#[cfg(feature = "std")]
+impl From<DecryptionError> for std::io::Error {
+    fn from(error: DecryptionError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Allows validation errors to flow through [`std::io::Result`], as [`std::io::ErrorKind::InvalidData`] errors.
+#[cfg(feature = "std")]
+impl From<ValidationError> for std::io::Error {
+    fn from(error: ValidationError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categorizes_decryption_errors() {
+        assert!(DecryptionError::Decryption.is_key_related());
+        assert!(DecryptionError::Decryption.is_corruption());
+
+        let error = DecryptionError::Base64Decoding(base64::DecodeError::InvalidPadding);
+        assert!(!error.is_key_related());
+        assert!(error.is_corruption());
+
+        let error = DecryptionError::UnsupportedVersion(42);
+        assert!(!error.is_key_related());
+        assert!(!error.is_corruption());
+    }
+
+    #[test]
+    fn converts_to_io_errors() {
+        let error = std::io::Error::from(DecryptionError::Decryption);
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(error.into_inner().unwrap().downcast::<DecryptionError>().as_deref(), Ok(DecryptionError::Decryption)));
+
+        let error = std::io::Error::from(EncryptionError::KeyIndexOutOfRange { index: 1, len: 1 });
+        assert_eq!(error.kind(), std::io::ErrorKind::Other);
+
+        let error = std::io::Error::from(ValidationError::InvalidLength { field: "nonce", expected: 24, actual: 12 });
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
 }
@@ -0,0 +1,49 @@
+//! Configurations with hardcoded keys, to test code using [`EncryptedMessage`](crate::EncryptedMessage)
+//! without defining your own configuration. Only available with the `testing` feature.
+//!
+//! **These are for tests only.** Their keys are public, so anything they encrypt can be decrypted by anyone.
+//! Enable the feature in your dev-dependencies, so it's never enabled in production builds:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! encrypted-message = { version = "0.3", features = ["testing"] }
+//! ```
+//!
+//! ```
+//! use encrypted_message::{EncryptedMessage, fixtures::TestConfigRandomized};
+//!
+//! let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo does pretty codes".to_string()).unwrap();
+//! assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::{config::{Config, Secret}, strategy::{Deterministic, Randomized}};
+
+/// A configuration using the [`Deterministic`] strategy, with two hardcoded keys.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigDeterministic;
+impl Config for TestConfigDeterministic {
+    type Strategy = Deterministic;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![
+            (*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into(),
+            (*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into(),
+        ]
+    }
+}
+
+/// A configuration using the [`Randomized`] strategy, with two hardcoded keys.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigRandomized;
+impl Config for TestConfigRandomized {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![
+            (*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into(),
+            (*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into(),
+        ]
+    }
+}
@@ -0,0 +1,148 @@
+//! A flat representation of [`EncryptedMessage`], with the headers next to the payload.
+
+use alloc::string::String;
+use core::{marker::PhantomData, ops::Deref};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{EncryptedMessage, EncryptedMessageHeaders, config::Config};
+
+/// Wraps an [`EncryptedMessage`], (de)serializing its headers next to the payload instead of nested in an `h` object,
+/// e.g. `{"p":"<payload>","iv":"<nonce>","at":"<tag>"}`.
+///
+/// This is useful for schemas where the fields must be at the top level, e.g. when flattening the message
+/// into a parent struct with `#[serde(flatten)]`. It dereferences to the wrapped message.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FlatEncryptedMessage<P, C: Config>(EncryptedMessage<P, C>);
+
+/// The flat representation, used to serialize a borrowed message.
+#[derive(Serialize)]
+struct FlatRepresentationRef<'a> {
+    #[serde(rename = "p")]
+    payload: &'a str,
+
+    #[serde(flatten)]
+    headers: &'a EncryptedMessageHeaders,
+}
+
+/// The flat representation, used to deserialize an owned message.
+#[derive(Deserialize)]
+struct FlatRepresentation {
+    #[serde(rename = "p")]
+    payload: String,
+
+    #[serde(flatten)]
+    headers: EncryptedMessageHeaders,
+}
+
+impl<P, C: Config> FlatEncryptedMessage<P, C> {
+    /// Wraps the provided message.
+    pub fn new(message: EncryptedMessage<P, C>) -> Self {
+        Self(message)
+    }
+
+    /// Returns the wrapped message.
+    pub fn into_inner(self) -> EncryptedMessage<P, C> {
+        self.0
+    }
+}
+
+impl<P, C: Config> From<EncryptedMessage<P, C>> for FlatEncryptedMessage<P, C> {
+    fn from(message: EncryptedMessage<P, C>) -> Self {
+        Self(message)
+    }
+}
+
+impl<P, C: Config> From<FlatEncryptedMessage<P, C>> for EncryptedMessage<P, C> {
+    fn from(message: FlatEncryptedMessage<P, C>) -> Self {
+        message.0
+    }
+}
+
+impl<P, C: Config> Deref for FlatEncryptedMessage<P, C> {
+    type Target = EncryptedMessage<P, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P, C: Config> Serialize for FlatEncryptedMessage<P, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FlatRepresentationRef { payload: &self.0.payload, headers: &self.0.headers }.serialize(serializer)
+    }
+}
+
+impl<'de, P, C: Config> Deserialize<'de> for FlatEncryptedMessage<P, C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let representation = FlatRepresentation::deserialize(deserializer)?;
+
+        Ok(Self(EncryptedMessage {
+            payload: representation.payload,
+            headers: representation.headers,
+            payload_type: PhantomData,
+            config: PhantomData,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::testing::TestConfigDeterministic;
+
+    #[test]
+    fn nested_and_flat_round_trip() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+
+        // Test the nested form.
+        let nested = serde_json::to_value(&message).unwrap();
+        assert_eq!(nested, json!({
+            "p": "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF",
+            "h": { "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0", "at": "uXQhmffPV/1D7qG8stw6vA==", "s": "d" },
+        }));
+        assert_eq!(serde_json::from_value::<EncryptedMessage<String, TestConfigDeterministic>>(nested).unwrap(), message);
+
+        // Test the flat form.
+        let flat = FlatEncryptedMessage::new(message);
+        let json = serde_json::to_value(&flat).unwrap();
+        assert_eq!(json, json!({
+            "p": "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF",
+            "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0",
+            "at": "uXQhmffPV/1D7qG8stw6vA==",
+            "s": "d",
+        }));
+
+        let parsed: FlatEncryptedMessage<String, TestConfigDeterministic> = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, flat);
+        assert_eq!(parsed.decrypt().unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn flattens_into_parent() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct User {
+            id: u32,
+            #[serde(flatten)]
+            secret: FlatEncryptedMessage<String, TestConfigDeterministic>,
+        }
+
+        let user = User {
+            id: 1,
+            secret: EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap().into(),
+        };
+
+        let json = serde_json::to_value(&user).unwrap();
+        assert_eq!(json, json!({
+            "id": 1,
+            "p": "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF",
+            "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0",
+            "at": "uXQhmffPV/1D7qG8stw6vA==",
+            "s": "d",
+        }));
+        assert_eq!(serde_json::from_value::<User>(json).unwrap(), user);
+    }
+}
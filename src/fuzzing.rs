@@ -0,0 +1,70 @@
+//! Entry points for fuzzers, only available when compiled with `--cfg fuzzing`, like `cargo fuzz` does.
+//!
+//! ```ignore
+//! #![no_main]
+//!
+//! libfuzzer_sys::fuzz_target!(|input: ([u8; 32], Vec<u8>)| {
+//!     let (key, payload) = input;
+//!     assert_eq!(encrypted_message::fuzzing::roundtrip(&payload, key), payload);
+//! });
+//! ```
+
+use alloc::{string::ToString as _, vec, vec::Vec};
+
+use secrecy::{ExposeSecret as _, Secret};
+use zeroize::Zeroizing;
+
+use crate::{EncryptedMessage, EncryptionSettings, config::Config, strategy::Randomized};
+
+/// The configuration used by [`roundtrip`], with a single key.
+#[derive(Debug)]
+struct FuzzingConfig(Secret<[u8; 32]>);
+
+impl Config for FuzzingConfig {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![Secret::new(*self.0.expose_secret())]
+    }
+}
+
+/// Encrypts the raw bytes with the provided key, writes the message as JSON & parses it back,
+/// then decrypts it, returning the decrypted bytes.
+///
+/// This skips payload serialization, so arbitrary bytes go through the encryption, base64 & JSON handling.
+///
+/// # Panics
+///
+/// Panics if the message can't be parsed or decrypted, which is always a bug.
+pub fn roundtrip(payload_bytes: &[u8], key: [u8; 32]) -> Vec<u8> {
+    let config = FuzzingConfig(Secret::new(key));
+    let settings = EncryptionSettings::from_config(&config).unwrap();
+
+    let message = EncryptedMessage::<(), FuzzingConfig>::encrypt_bytes(Zeroizing::new(payload_bytes.to_vec()), &settings, &config.primary_key());
+    let message: EncryptedMessage<(), FuzzingConfig> = message.to_string().parse().unwrap();
+
+    let (_, payload) = message.decrypt_bytes(&config).unwrap();
+    payload.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::testing::TestConfigRandomized;
+
+    proptest! {
+        #[test]
+        fn roundtrips_arbitrary_bytes(payload in any::<Vec<u8>>(), key in any::<[u8; 32]>()) {
+            prop_assert_eq!(roundtrip(&payload, key), payload);
+        }
+
+        #[test]
+        fn roundtrips_arbitrary_strings(payload in any::<alloc::string::String>()) {
+            let message = EncryptedMessage::<_, TestConfigRandomized>::encrypt(payload.clone()).unwrap();
+            prop_assert_eq!(message.decrypt().unwrap(), payload);
+        }
+    }
+}
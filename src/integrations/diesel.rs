@@ -1,31 +1,32 @@
-use std::fmt::Debug;
-
 use diesel::{
     deserialize::FromSql,
     serialize::ToSql,
     backend::Backend,
     sql_types,
 };
-use serde::{Serialize, de::DeserializeOwned};
+
+#[cfg(any(feature = "diesel-postgres", all(feature = "diesel-binary", feature = "diesel-mysql")))]
+use std::io::Write as _;
 
 use crate::{EncryptedMessage, config::Config};
 
+// Messages are (de)serialized directly from & to the raw JSON sent by the backend,
+// without going through an intermediate `serde_json::Value`.
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-postgres"))]
 macro_rules! impl_from_and_to_sql {
-    ($($sql_type:ty, $backend:ty),+ $(,)?) => {
+    ($($backend:ty),+ $(,)?) => {
         $(
-            impl<P: Debug + DeserializeOwned + Serialize, C: Config> FromSql<$sql_type, $backend> for EncryptedMessage<P, C> {
+            impl<P, C: Config> FromSql<sql_types::Json, $backend> for EncryptedMessage<P, C> {
                 fn from_sql(value: <$backend as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
-                    let json: serde_json::Value = FromSql::<$sql_type, $backend>::from_sql(value)?;
-
-                    Ok(serde_json::from_value(json)?)
+                    Ok(serde_json::from_slice(value.as_bytes())?)
                 }
             }
 
-            impl<P: Debug + DeserializeOwned + Serialize, C: Config> ToSql<$sql_type, $backend> for EncryptedMessage<P, C> {
+            impl<P, C: Config> ToSql<sql_types::Json, $backend> for EncryptedMessage<P, C> {
                 fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, $backend>) -> diesel::serialize::Result {
-                    let json = serde_json::to_value(self)?;
+                    serde_json::to_writer(out, self)?;
 
-                    ToSql::<$sql_type, $backend>::to_sql(&json, &mut out.reborrow())
+                    Ok(diesel::serialize::IsNull::No)
                 }
             }
         )+
@@ -33,10 +34,100 @@ macro_rules! impl_from_and_to_sql {
 }
 
 #[cfg(feature = "diesel-mysql")]
-impl_from_and_to_sql!(sql_types::Json, diesel::mysql::Mysql);
+impl_from_and_to_sql!(diesel::mysql::Mysql);
+
+#[cfg(feature = "diesel-postgres")]
+impl_from_and_to_sql!(diesel::pg::Pg);
+
+/// The version of PostgreSQL's binary `JSONB` format, which prefixes the JSON text.
+#[cfg(feature = "diesel-postgres")]
+const JSONB_VERSION: u8 = 1;
+
+#[cfg(feature = "diesel-postgres")]
+impl<P, C: Config> FromSql<sql_types::Jsonb, diesel::pg::Pg> for EncryptedMessage<P, C> {
+    fn from_sql(value: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        let Some((&JSONB_VERSION, json)) = value.as_bytes().split_first() else {
+            return Err("Unsupported JSONB encoding version.".into());
+        };
+
+        Ok(serde_json::from_slice(json)?)
+    }
+}
 
 #[cfg(feature = "diesel-postgres")]
-impl_from_and_to_sql!(
-    sql_types::Json, diesel::pg::Pg,
-    sql_types::Jsonb, diesel::pg::Pg,
-);
+impl<P, C: Config> ToSql<sql_types::Jsonb, diesel::pg::Pg> for EncryptedMessage<P, C> {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>) -> diesel::serialize::Result {
+        out.write_all(&[JSONB_VERSION])?;
+        serde_json::to_writer(out, self)?;
+
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+// SQLite has no JSON type, so messages are stored as their compact JSON representation in a `Text` column.
+#[cfg(feature = "diesel-sqlite")]
+impl<P, C: Config> FromSql<sql_types::Text, diesel::sqlite::Sqlite> for EncryptedMessage<P, C> {
+    fn from_sql(value: <diesel::sqlite::Sqlite as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let json: String = FromSql::<sql_types::Text, diesel::sqlite::Sqlite>::from_sql(value)?;
+
+        Ok(json.parse()?)
+    }
+}
+
+#[cfg(feature = "diesel-sqlite")]
+impl<P, C: Config> ToSql<sql_types::Text, diesel::sqlite::Sqlite> for EncryptedMessage<P, C> {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.to_string());
+
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+// With the `diesel-binary` feature, messages can also be stored in their compact binary representation,
+// see `EncryptedMessage::to_bytes`, which avoids parsing JSON on every read.
+#[cfg(all(feature = "diesel-binary", any(feature = "diesel-mysql", feature = "diesel-postgres")))]
+macro_rules! impl_from_and_to_binary_sql {
+    ($($backend:ty),+ $(,)?) => {
+        $(
+            impl<P, C: Config> FromSql<sql_types::Binary, $backend> for EncryptedMessage<P, C> {
+                fn from_sql(value: <$backend as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+                    let bytes: Vec<u8> = FromSql::<sql_types::Binary, $backend>::from_sql(value)?;
+
+                    Ok(Self::from_bytes(&bytes)?)
+                }
+            }
+
+            impl<P, C: Config> ToSql<sql_types::Binary, $backend> for EncryptedMessage<P, C> {
+                fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, $backend>) -> diesel::serialize::Result {
+                    out.write_all(&self.to_bytes()?)?;
+
+                    Ok(diesel::serialize::IsNull::No)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(all(feature = "diesel-binary", feature = "diesel-mysql"))]
+impl_from_and_to_binary_sql!(diesel::mysql::Mysql);
+
+#[cfg(all(feature = "diesel-binary", feature = "diesel-postgres"))]
+impl_from_and_to_binary_sql!(diesel::pg::Pg);
+
+#[cfg(all(feature = "diesel-binary", feature = "diesel-sqlite"))]
+impl<P, C: Config> FromSql<sql_types::Binary, diesel::sqlite::Sqlite> for EncryptedMessage<P, C> {
+    fn from_sql(value: <diesel::sqlite::Sqlite as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let bytes: Vec<u8> = FromSql::<sql_types::Binary, diesel::sqlite::Sqlite>::from_sql(value)?;
+
+        Ok(Self::from_bytes(&bytes)?)
+    }
+}
+
+#[cfg(all(feature = "diesel-binary", feature = "diesel-sqlite"))]
+impl<P, C: Config> ToSql<sql_types::Binary, diesel::sqlite::Sqlite> for EncryptedMessage<P, C> {
+    fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.to_bytes()?);
+
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
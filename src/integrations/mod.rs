@@ -1,2 +1,8 @@
 #[cfg(feature = "diesel")]
 mod diesel;
+
+#[cfg(feature = "tokio")]
+mod tokio;
+
+#[cfg(feature = "utoipa")]
+mod utoipa;
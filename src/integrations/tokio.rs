@@ -0,0 +1,109 @@
+use core::{fmt::Debug, future::Future, marker::PhantomData};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::task::{JoinHandle, spawn_blocking};
+
+use crate::{EncryptedMessage, EncryptionError, DecryptionError, config::Config};
+
+impl<P, C> EncryptedMessage<P, C>
+where
+    P: Debug + DeserializeOwned + Serialize + Send + 'static,
+    C: Config + Send + 'static,
+{
+    /// Creates an [`EncryptedMessage`] like [`EncryptedMessage::encrypt_with_config`], on Tokio's blocking thread pool.
+    ///
+    /// This keeps the async runtime responsive when [`Config::keys`] is expensive, e.g. when keys are derived with PBKDF2.
+    /// The configuration is moved into the blocking task, & the returned future doesn't borrow anything.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::encrypt_with_config`].
+    ///
+    /// # Panics
+    ///
+    /// The future panics if it's polled outside of a Tokio runtime, or if the encryption panics.
+    pub fn encrypt_with_config_blocking(payload: P, config: C) -> impl Future<Output = Result<Self, EncryptionError>> + Send + 'static {
+        let encrypt = move || Self::encrypt_with_config(payload, &config);
+
+        async move { join(spawn_blocking(encrypt)).await }
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`] like [`EncryptedMessage::decrypt_with_config`], on Tokio's blocking thread pool.
+    ///
+    /// The message is copied, so the returned future doesn't borrow it. See [`EncryptedMessage::encrypt_with_config_blocking`].
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    ///
+    /// # Panics
+    ///
+    /// The future panics if it's polled outside of a Tokio runtime, or if the decryption panics.
+    pub fn decrypt_with_config_blocking(&self, config: C) -> impl Future<Output = Result<P, DecryptionError>> + Send + 'static {
+        let message = EncryptedMessage::<P, C> {
+            payload: self.payload.clone(),
+            headers: self.headers.clone(),
+            payload_type: PhantomData,
+            config: PhantomData,
+        };
+
+        async move { join(spawn_blocking(move || message.decrypt_with_config(&config))).await }
+    }
+}
+
+impl<P, C> EncryptedMessage<P, C>
+where
+    P: Debug + DeserializeOwned + Serialize + Send + 'static,
+    C: Config + Default + Send + 'static,
+{
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_config_blocking`],
+    /// passing `C::default()` as the configuration.
+    pub fn encrypt_blocking(payload: P) -> impl Future<Output = Result<Self, EncryptionError>> + Send + 'static {
+        Self::encrypt_with_config_blocking(payload, C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_with_config_blocking`],
+    /// passing `C::default()` as the configuration.
+    pub fn decrypt_blocking(&self) -> impl Future<Output = Result<P, DecryptionError>> + Send + 'static {
+        self.decrypt_with_config_blocking(C::default())
+    }
+}
+
+/// Waits for a blocking task, resuming its panic if it panicked.
+async fn join<T>(task: JoinHandle<T>) -> T {
+    match task.await {
+        Ok(value) => value,
+        Err(error) => match error.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(_) => panic!("The blocking task was cancelled, as the runtime is shutting down."),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{TestConfigDeterministic, TestConfigRandomized};
+
+    #[tokio::test]
+    async fn encrypt_and_decrypt_blocking() {
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt_blocking("rigo does pretty codes".to_string()).await.unwrap();
+        assert_eq!(message.decrypt_blocking().await.unwrap(), "rigo does pretty codes");
+
+        // Test that the blocking methods produce the same messages.
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_blocking("rigo does pretty codes".to_string()).await.unwrap();
+        assert_eq!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn futures_are_owned() {
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo does pretty codes".to_string()).unwrap();
+
+        // The future can outlive the message.
+        let future = message.decrypt_blocking();
+        drop(message);
+
+        assert_eq!(future.await.unwrap(), "rigo does pretty codes");
+    }
+}
@@ -0,0 +1,93 @@
+use alloc::borrow::Cow;
+
+use utoipa::{
+    PartialSchema,
+    ToSchema,
+    openapi::{
+        RefOr,
+        schema::{ArrayBuilder, ObjectBuilder, Schema, Type},
+    },
+};
+
+use crate::{EncryptedMessage, config::Config};
+
+// The schema describes the same JSON representation as `FORMAT`, which should be kept in sync.
+impl<P, C: Config> PartialSchema for EncryptedMessage<P, C> {
+    fn schema() -> RefOr<Schema> {
+        let headers = ObjectBuilder::new()
+            .description(Some("The headers stored with the encrypted payload."))
+            .property("v", integer("The version of the message format. Omitted for version 0.").minimum(Some(0)).maximum(Some(255)))
+            .property("iv", base64("The 24-byte nonce used to encrypt the payload."))
+            .required("iv")
+            .property("at", base64("The 16-byte auth tag. Omitted when it's appended to the encrypted payload."))
+            .property("e", string("The format the payload was serialized with before encryption. Omitted for JSON, & authenticated otherwise.").enum_values(Some(["json", "cbor", "postcard"])))
+            .property("s", string("The strategy the payload was encrypted with: deterministic, randomized or counter.").enum_values(Some(["d", "r", "c"])))
+            .property("ts", integer("The time the payload was encrypted at, in seconds since the Unix epoch."))
+            .property(
+                "m",
+                ObjectBuilder::new()
+                    .schema_type(Type::Object)
+                    .description(Some("Non-secret metadata, authenticated as associated data. Omitted when empty."))
+                    .additional_properties(Some(ObjectBuilder::new().schema_type(Type::String))),
+            )
+            .property("pd", integer("The block size the serialized payload was padded to before encryption, authenticated as associated data.").minimum(Some(1)).maximum(Some(65536)))
+            .property("kc", base64("The commitment to the key the payload was encrypted with. Required from version 2."))
+            .property("ht", base64("The 32-byte HMAC-SHA256 tag of the encrypted payload, verified before decryption when present."))
+            .property(
+                "rk",
+                ArrayBuilder::new()
+                    .description(Some("The data key of the payload, wrapped under the key of each recipient. Omitted for messages encrypted with a single key."))
+                    .items(base64("The 24-byte nonce, followed by the encrypted 32-byte data key & its 16-byte auth tag."))
+                    .min_items(Some(1)),
+            );
+
+        ObjectBuilder::new()
+            .description(Some("A payload encrypted with XChaCha20Poly1305. All binary fields use standard base64 with padding."))
+            .property("p", base64("The encrypted payload. When the `at` header is omitted, the 16-byte auth tag is appended to it."))
+            .required("p")
+            .property("h", headers)
+            .required("h")
+            .into()
+    }
+}
+
+// Messages have the same representation regardless of their payload & configuration types, so they share a schema.
+impl<P, C: Config> ToSchema for EncryptedMessage<P, C> {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("EncryptedMessage")
+    }
+}
+
+fn string(description: &str) -> ObjectBuilder {
+    ObjectBuilder::new().schema_type(Type::String).description(Some(description))
+}
+
+fn base64(description: &str) -> ObjectBuilder {
+    string(description).content_encoding("base64")
+}
+
+fn integer(description: &str) -> ObjectBuilder {
+    ObjectBuilder::new().schema_type(Type::Integer).description(Some(description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TestConfigDeterministic;
+
+    #[test]
+    fn describes_json_representation() {
+        assert_eq!(EncryptedMessage::<String, TestConfigDeterministic>::name(), "EncryptedMessage");
+
+        let schema = serde_json::to_value(EncryptedMessage::<String, TestConfigDeterministic>::schema()).unwrap();
+        assert_eq!(schema["required"], serde_json::json!(["p", "h"]));
+        assert_eq!(schema["properties"]["p"]["contentEncoding"], "base64");
+        assert_eq!(schema["properties"]["h"]["required"], serde_json::json!(["iv"]));
+
+        // Test that the headers match the ones described by `FORMAT`.
+        let format: serde_json::Value = serde_json::from_str(crate::FORMAT).unwrap();
+        let headers = schema["properties"]["h"]["properties"].as_object().unwrap();
+        assert!(headers.keys().eq(format["properties"]["h"]["properties"].as_object().unwrap().keys()));
+    }
+}
@@ -0,0 +1,249 @@
+//! Utilities to generate secure keys for your [`Config`](crate::config::Config).
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write as _;
+
+use hkdf::Hkdf;
+use secrecy::{ExposeSecret as _, Secret};
+use zeroize::Zeroize as _;
+#[cfg(feature = "bip39")]
+use zeroize::Zeroizing;
+use sha2::Sha256;
+
+use crate::{
+    strategy::{Strategy, StrategyKind},
+    utilities::{base64, random},
+};
+#[cfg(feature = "bip39")]
+use crate::error::MnemonicError;
+
+/// Generates a random 32-byte key, using a cryptographically secure random number generator.
+pub fn generate_key() -> Secret<[u8; 32]> {
+    Secret::new(random::random())
+}
+
+/// Generates a random 32-byte key, & returns it as a hex-encoded string.
+pub fn generate_key_hex() -> Secret<String> {
+    let key = generate_key();
+
+    let mut hex_key = String::with_capacity(64);
+    for byte in key.expose_secret() {
+        write!(hex_key, "{byte:02x}").unwrap();
+    }
+
+    Secret::new(hex_key)
+}
+
+/// Generates a random 32-byte key, & returns it as a base64-encoded string.
+pub fn generate_key_base64() -> Secret<String> {
+    Secret::new(base64::encode(generate_key().expose_secret()))
+}
+
+/// Copies a key into a [`Secret`], & zeroizes the provided array.
+///
+/// Arrays are `Copy`, so converting a derived key with `.into()` leaves the original bytes in memory. This is useful in
+/// [`Config::keys`](crate::config::Config::keys) implementations deriving their keys, e.g. with PBKDF2:
+///
+/// ```
+/// use encrypted_message::{config::{Config, Secret}, key_generation::into_secret, strategy::Randomized};
+/// use pbkdf2::pbkdf2_hmac_array;
+/// use sha2::Sha256;
+///
+/// #[derive(Debug)]
+/// struct PasswordConfig;
+/// impl Config for PasswordConfig {
+///     type Strategy = Randomized;
+///
+///     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+///         let mut key = pbkdf2_hmac_array::<Sha256, 32>(b"human-password", b"unique-salt", 2_u32.pow(16));
+///
+///         vec![into_secret(&mut key)]
+///     }
+/// }
+/// ```
+pub fn into_secret(key: &mut [u8; 32]) -> Secret<[u8; 32]> {
+    let secret = Secret::new(*key);
+    key.zeroize();
+
+    secret
+}
+
+/// Normalizes a key of any length into a 32-byte key, using HKDF-SHA256 extract.
+///
+/// This is useful when integrating with systems that provide keys that aren't 32 bytes long,
+/// & can be used in [`Config::keys`](crate::config::Config::keys), see [`normalize_keys`]. The same input always produces the same key.
+/// Note that this doesn't add entropy, the input key must still be generated securely.
+pub fn normalize_key(key: &[u8]) -> Secret<[u8; 32]> {
+    let (mut normalized_key, _) = Hkdf::<Sha256>::extract(None, key);
+    let secret = into_secret(&mut normalized_key.into());
+    normalized_key.as_mut_slice().zeroize();
+
+    secret
+}
+
+/// Normalizes keys of any length into 32-byte keys, in the same order, with [`normalize_key`].
+///
+/// This is meant to be returned from [`Config::keys`](crate::config::Config::keys), when integrating with systems
+/// that provide keys of other lengths, e.g. 64-byte keys. Normalized keys differ from their raw keys,
+/// even for 32-byte raw keys, so a configuration must consistently normalize its keys, or not.
+///
+/// ```
+/// use encrypted_message::{config::{Config, Secret}, key_generation::normalize_keys, strategy::Randomized};
+///
+/// #[derive(Debug)]
+/// struct ExternalConfig;
+/// impl Config for ExternalConfig {
+///     type Strategy = Randomized;
+///
+///     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+///         normalize_keys(&[Secret::new(vec![7; 64])])
+///     }
+/// }
+/// ```
+pub fn normalize_keys(keys: &[Secret<Vec<u8>>]) -> Vec<Secret<[u8; 32]>> {
+    keys.iter().map(|key| normalize_key(key.expose_secret())).collect()
+}
+
+/// Derives a 32-byte key from a BIP39 mnemonic phrase, e.g. a recovery phrase written down by a user.
+/// Only available with the `bip39` feature.
+///
+/// The phrase is converted to its 64-byte seed with the standard BIP39 derivation, with an empty passphrase,
+/// & the seed is normalized into a key with [`normalize_key`]. The same phrase always produces the same key.
+///
+/// ```
+/// use encrypted_message::{config::ExposeSecret as _, key_generation::from_mnemonic};
+///
+/// let key = from_mnemonic("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+/// assert_eq!(key.expose_secret().len(), 32);
+/// ```
+///
+/// # Errors
+///
+/// Returns a [`MnemonicError::Invalid`] error if the phrase isn't a valid BIP39 mnemonic, in any of the supported languages.
+#[cfg(feature = "bip39")]
+pub fn from_mnemonic(phrase: &str) -> Result<Secret<[u8; 32]>, MnemonicError> {
+    let mnemonic = bip39::Mnemonic::parse(phrase)?;
+    let seed = Zeroizing::new(mnemonic.to_seed(""));
+
+    Ok(normalize_key(seed.as_slice()))
+}
+
+/// Derives a key for the strategy `S` from a master key, using HKDF-SHA256 with the label of the strategy,
+/// i.e. `b"deterministic"`, `b"randomized"` or `b"counter"`.
+///
+/// The strategies shouldn't share keys, see [`Config::assert_key_separation`](crate::config::Config::assert_key_separation),
+/// so this allows a single master key to safely power configurations using different strategies:
+///
+/// ```
+/// use encrypted_message::{
+///     config::{Config, Secret},
+///     key_generation::derive_strategy_key,
+///     strategy::Deterministic,
+/// };
+///
+/// #[derive(Debug)]
+/// struct DeterministicConfig;
+/// impl Config for DeterministicConfig {
+///     type Strategy = Deterministic;
+///
+///     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+///         let master_key = Secret::new(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW");
+///
+///         vec![derive_strategy_key::<Self::Strategy>(&master_key)]
+///     }
+/// }
+/// ```
+pub fn derive_strategy_key<S: Strategy>(master_key: &Secret<[u8; 32]>) -> Secret<[u8; 32]> {
+    let label: &[u8] = match S::KIND {
+        StrategyKind::Deterministic => b"deterministic",
+        StrategyKind::Randomized => b"randomized",
+        StrategyKind::Counter => b"counter",
+    };
+
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(None, master_key.expose_secret()).expand(label, &mut key).unwrap();
+
+    into_secret(&mut key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_random_keys() {
+        // Test that the keys never match.
+        assert_ne!(generate_key().expose_secret(), generate_key().expose_secret());
+    }
+
+    #[test]
+    fn generates_hex_keys() {
+        let hex_key = generate_key_hex();
+        assert_eq!(hex_key.expose_secret().len(), 64);
+
+        let mut key = [0; 32];
+        assert!(hex::decode_to_slice(hex_key.expose_secret(), &mut key).is_ok());
+    }
+
+    #[test]
+    fn generates_base64_keys() {
+        let base64_key = generate_key_base64();
+        assert_eq!(base64::decode(base64_key.expose_secret()).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn derives_strategy_keys() {
+        use crate::strategy::{Counter, Deterministic, Randomized};
+
+        let master_key = Secret::new(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW");
+        assert_eq!(
+            hex::encode(derive_strategy_key::<Deterministic>(&master_key).expose_secret()),
+            "49318f5e4d296f805464bc94883597e0e2d71098e5c533edec046f802a7b74c6",
+        );
+        assert_eq!(
+            hex::encode(derive_strategy_key::<Randomized>(&master_key).expose_secret()),
+            "2ff11eda8c324dabd5ceece51c0f95f0e2629ddfaff473dd0af994081c98dca9",
+        );
+        assert_eq!(
+            hex::encode(derive_strategy_key::<Counter>(&master_key).expose_secret()),
+            "f7e735a09ce563a1eaf12a75d2cadc0b8d0b0a6216751132fd8dae3659356f65",
+        );
+    }
+
+    #[cfg(feature = "bip39")]
+    #[test]
+    fn derives_keys_from_mnemonics() {
+        // The BIP39 test vector, with the seed `5eb00bbd…ce9e38e4`.
+        let key = from_mnemonic("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+        assert_eq!(hex::encode(key.expose_secret()), "d7b49adc3237e6859b427bf82a6199bdb43331b58538f7ce5eda48a8ce259652");
+
+        // Test that invalid checksums & unknown words are rejected.
+        assert!(matches!(
+            from_mnemonic("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon").unwrap_err(),
+            MnemonicError::Invalid(_),
+        ));
+        assert!(from_mnemonic("rigo does pretty codes rigo does pretty codes rigo does pretty codes").is_err());
+    }
+
+    #[test]
+    fn moves_keys_into_secrets() {
+        let mut key = [1; 32];
+        assert_eq!(into_secret(&mut key).expose_secret(), &[1; 32]);
+        assert_eq!(key, [0; 32]);
+    }
+
+    #[test]
+    fn normalizes_keys() {
+        let key = [1; 64];
+        assert_eq!(normalize_key(&key).expose_secret(), normalize_key(&key).expose_secret());
+        assert_ne!(normalize_key(&key).expose_secret(), normalize_key(&[1; 16]).expose_secret());
+
+        // Test that 32-byte keys are normalized too.
+        assert_ne!(normalize_key(&[1; 32]).expose_secret(), &[1; 32]);
+
+        let keys = normalize_keys(&[Secret::new(vec![1; 64]), Secret::new(vec![2; 16])]);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].expose_secret(), normalize_key(&[1; 64]).expose_secret());
+        assert_eq!(keys[1].expose_secret(), normalize_key(&[2; 16]).expose_secret());
+    }
+}
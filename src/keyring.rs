@@ -0,0 +1,194 @@
+//! Contains the [`Keyring`] type, used to manage identified keys & their rotation.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use secrecy::{ExposeSecret as _, Secret};
+use serde::Deserialize;
+use zeroize::{Zeroize as _, Zeroizing};
+
+use crate::{error::KeyringError, key_generation::into_secret, utilities::base64};
+
+#[cfg(feature = "tink")]
+mod tink;
+
+/// A set of keys identified by ids, with a designated primary key.
+///
+/// Keyrings are usually loaded from a JSON document, where keys are either base64 or hex-encoded:
+///
+/// ```
+/// use encrypted_message::keyring::Keyring;
+///
+/// let keyring = Keyring::from_json(r#"{
+///     "primary": "2024-06",
+///     "keys": {
+///         "2024-01": "dGl3UUNXS0NzVzFkNnF6WmZwN0hZdm5ScVpQWVloTXQ=",
+///         "2024-06": "303132333435363738396162636465666768696a6b6c6d6e6f70717273747576"
+///     }
+/// }"#).unwrap();
+///
+/// assert_eq!(keyring.primary_id(), "2024-06");
+/// assert_eq!(keyring.keys().len(), 2);
+/// ```
+///
+/// It implements [`Deserialize`], so it can also be loaded from other formats, like TOML.
+/// With the `tink` feature enabled, it can also be imported from a Google Tink keyset, see `Keyring::from_tink_json`.
+/// Rotating keys then only requires adding a key to the keyring, making it the primary key,
+/// & re-encrypting existing messages with [`rotate`](crate::rotate).
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "KeyringDocument")]
+pub struct Keyring {
+    primary: String,
+    keys: BTreeMap<String, Secret<[u8; 32]>>,
+}
+
+/// The document a [`Keyring`] is parsed from.
+#[derive(Deserialize)]
+struct KeyringDocument {
+    primary: String,
+    keys: BTreeMap<String, String>,
+}
+
+impl Keyring {
+    /// Parses a keyring from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`KeyringError::Json`] error if the JSON doesn't have the expected structure.
+    /// - Returns a [`KeyringError::InvalidKey`] error if a key isn't a base64 or hex-encoded 32-byte key.
+    /// - Returns a [`KeyringError::MissingPrimaryKey`] error if the primary key isn't in the keyring.
+    pub fn from_json(json: &str) -> Result<Self, KeyringError> {
+        let document: KeyringDocument = serde_json::from_str(json)?;
+
+        Self::try_from(document)
+    }
+
+    /// Returns the id of the primary key.
+    pub fn primary_id(&self) -> &str {
+        &self.primary
+    }
+
+    /// Returns the key with the provided id, if it's in the keyring.
+    pub fn key(&self, id: &str) -> Option<Secret<[u8; 32]>> {
+        self.keys.get(id).map(|key| Secret::new(*key.expose_secret()))
+    }
+
+    /// Returns the keys in the order expected by [`Config::keys`](crate::config::Config::keys),
+    /// the primary key first, followed by the other keys in the order of their ids.
+    pub fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        let primary = self.keys.get(&self.primary).into_iter();
+        let others = self.keys.iter().filter(|(id, _)| **id != self.primary).map(|(_, key)| key);
+
+        // The vector is allocated upfront, so it's never reallocated, leaving copies of the keys in freed memory.
+        let mut keys = Vec::with_capacity(self.keys.len());
+        keys.extend(primary.chain(others).map(|key| Secret::new(*key.expose_secret())));
+
+        keys
+    }
+}
+
+impl TryFrom<KeyringDocument> for Keyring {
+    type Error = KeyringError;
+
+    fn try_from(document: KeyringDocument) -> Result<Self, Self::Error> {
+        // All encoded keys are decoded before returning any error, so they're all zeroized.
+        let mut keys = BTreeMap::new();
+        let mut invalid_id = None;
+        for (id, mut encoded_key) in document.keys {
+            match decode_key(&encoded_key) {
+                Some(mut key) => {
+                    keys.insert(id, into_secret(&mut key));
+                },
+                None => {
+                    invalid_id.get_or_insert(id);
+                },
+            }
+            encoded_key.zeroize();
+        }
+
+        if let Some(id) = invalid_id {
+            return Err(KeyringError::InvalidKey { id });
+        }
+
+        if !keys.contains_key(&document.primary) {
+            return Err(KeyringError::MissingPrimaryKey { id: document.primary });
+        }
+
+        Ok(Self { primary: document.primary, keys })
+    }
+}
+
+/// Decodes a 32-byte key, which is hex-encoded if it's 64 characters long, & base64-encoded otherwise.
+fn decode_key(encoded_key: &str) -> Option<[u8; 32]> {
+    let bytes = if encoded_key.len() == 64 {
+        decode_hex(encoded_key)?
+    } else {
+        Zeroizing::new(base64::decode(encoded_key).ok()?)
+    };
+
+    bytes.as_slice().try_into().ok()
+}
+
+/// Decodes a hex-encoded string.
+fn decode_hex(hex: &str) -> Option<Zeroizing<Vec<u8>>> {
+    // `u8::from_str_radix` accepts a leading `+`, so the digits are checked first.
+    if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = Zeroizing::new(Vec::with_capacity(hex.len() / 2));
+    for pair in hex.as_bytes().chunks(2) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        bytes.push(u8::from_str_radix(pair, 16).ok()?);
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYRING: &str = r#"{
+        "primary": "b",
+        "keys": {
+            "a": "dGl3UUNXS0NzVzFkNnF6WmZwN0hZdm5ScVpQWVloTXQ=",
+            "b": "303132333435363738396162636465666768696a6b6c6d6e6f70717273747576",
+            "c": "dXVPeGZwV2dSZ0lFbzNkSXJkbzBobkhKSEYxaG50dlc="
+        }
+    }"#;
+
+    #[test]
+    fn keys_start_with_primary() {
+        let keyring = Keyring::from_json(KEYRING).unwrap();
+        assert_eq!(keyring.primary_id(), "b");
+
+        let keys = keyring.keys();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0].expose_secret(), b"0123456789abcdefghijklmnopqrstuv");
+        assert_eq!(keys[1].expose_secret(), b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt");
+        assert_eq!(keys[2].expose_secret(), b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW");
+
+        assert_eq!(keyring.key("a").unwrap().expose_secret(), b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt");
+        assert!(keyring.key("d").is_none());
+    }
+
+    #[test]
+    fn test_invalid_keyrings() {
+        assert!(matches!(Keyring::from_json("{}").unwrap_err(), KeyringError::Json(_)));
+        assert!(matches!(
+            Keyring::from_json(r#"{ "primary": "a", "keys": { "a": "c2hvcnQ=" } }"#).unwrap_err(),
+            KeyringError::InvalidKey { id } if id == "a",
+        ));
+        assert!(matches!(
+            Keyring::from_json(r#"{ "primary": "b", "keys": { "a": "dXVPeGZwV2dSZ0lFbzNkSXJkbzBobkhKSEYxaG50dlc=" } }"#).unwrap_err(),
+            KeyringError::MissingPrimaryKey { id } if id == "b",
+        ));
+
+        // Test that hex-encoded keys only contain hex digits.
+        let signed_key = "+f".repeat(32);
+        assert!(matches!(
+            Keyring::from_json(&format!(r#"{{ "primary": "a", "keys": {{ "a": "{signed_key}" }} }}"#)).unwrap_err(),
+            KeyringError::InvalidKey { id } if id == "a",
+        ));
+    }
+}
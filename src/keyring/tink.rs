@@ -0,0 +1,219 @@
+//! Imports keys from [Google Tink](https://developers.google.com/tink) cleartext keysets.
+
+use alloc::{collections::BTreeMap, string::{String, ToString as _}, vec::Vec};
+
+use serde::Deserialize;
+use zeroize::{Zeroize as _, Zeroizing};
+
+use super::Keyring;
+use crate::{error::KeyringError, key_generation::into_secret, utilities::base64};
+
+/// The type URLs of the supported Tink AEAD keys, which store the raw key material in the same field.
+const SUPPORTED_TYPE_URLS: [&str; 2] = [
+    "type.googleapis.com/google.crypto.tink.XChaCha20Poly1305Key",
+    "type.googleapis.com/google.crypto.tink.AesGcmKey",
+];
+
+/// A Tink cleartext keyset, in its JSON representation.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TinkKeyset {
+    primary_key_id: u32,
+    key: Vec<TinkKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TinkKey {
+    key_data: TinkKeyData,
+    status: String,
+    key_id: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TinkKeyData {
+    type_url: String,
+    value: String,
+}
+
+impl Keyring {
+    /// Parses a keyring from a Tink cleartext keyset, in its JSON representation, e.g. as written by `tinkey`.
+    ///
+    /// The raw key material of the enabled XChaCha20Poly1305 & AES-256-GCM keys is imported, identified by their key ids,
+    /// & the primary key of the keyset is the primary key of the keyring. Disabled & destroyed keys are skipped.
+    ///
+    /// This allows pointing [`Config::keys`](crate::config::Config::keys) at an existing keyset while migrating off Tink.
+    /// **Messages encrypted by Tink can't be decrypted**, as Tink uses its own ciphertext format, so they must be
+    /// decrypted with Tink & re-encrypted.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`KeyringError::Json`] error if the JSON doesn't have the expected structure.
+    /// - Returns a [`KeyringError::UnsupportedTinkKey`] error if an enabled key isn't a 32-byte XChaCha20Poly1305 or AES-GCM key.
+    /// - Returns a [`KeyringError::MissingPrimaryKey`] error if the primary key isn't an enabled key of the keyset.
+    pub fn from_tink_json(json: &str) -> Result<Self, KeyringError> {
+        let keyset: TinkKeyset = serde_json::from_str(json)?;
+
+        // All encoded keys are decoded before returning any error, so they're all zeroized.
+        let mut keys = BTreeMap::new();
+        let mut unsupported_key = None;
+        for mut tink_key in keyset.key {
+            if tink_key.status == "ENABLED" {
+                match decode_key(&tink_key.key_data) {
+                    Some(mut key) => {
+                        keys.insert(tink_key.key_id.to_string(), into_secret(&mut key));
+                    },
+                    None => {
+                        unsupported_key.get_or_insert((tink_key.key_id, core::mem::take(&mut tink_key.key_data.type_url)));
+                    },
+                }
+            }
+            tink_key.key_data.value.zeroize();
+        }
+
+        if let Some((id, type_url)) = unsupported_key {
+            return Err(KeyringError::UnsupportedTinkKey { id: id.to_string(), type_url });
+        }
+
+        let primary = keyset.primary_key_id.to_string();
+        if !keys.contains_key(&primary) {
+            return Err(KeyringError::MissingPrimaryKey { id: primary });
+        }
+
+        Ok(Self { primary, keys })
+    }
+}
+
+/// Decodes the raw material of a supported 32-byte key.
+fn decode_key(key_data: &TinkKeyData) -> Option<[u8; 32]> {
+    if !SUPPORTED_TYPE_URLS.contains(&key_data.type_url.as_str()) {
+        return None;
+    }
+
+    let serialized_key = Zeroizing::new(base64::decode(&key_data.value).ok()?);
+
+    key_value(&serialized_key)?.try_into().ok()
+}
+
+/// Returns the `key_value` field of a serialized Tink key, which is field 3 of both supported key protobufs.
+fn key_value(mut bytes: &[u8]) -> Option<&[u8]> {
+    let mut key_value = None;
+    while !bytes.is_empty() {
+        let tag = read_varint(&mut bytes)?;
+        match (tag >> 3, tag & 0b111) {
+            // Varint fields, like the key version.
+            (_, 0) => {
+                read_varint(&mut bytes)?;
+            },
+            // Length-delimited fields, like the key value & its parameters.
+            (field, 2) => {
+                let len = usize::try_from(read_varint(&mut bytes)?).ok()?;
+                if len > bytes.len() {
+                    return None;
+                }
+
+                let (value, rest) = bytes.split_at(len);
+                if field == 3 {
+                    key_value = Some(value);
+                }
+                bytes = rest;
+            },
+            _ => return None,
+        }
+    }
+
+    key_value
+}
+
+/// Reads a protobuf varint, advancing the provided bytes past it.
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secrecy::ExposeSecret as _;
+
+    const KEYSET: &str = r#"{
+        "primaryKeyId": 2002,
+        "key": [
+            {
+                "keyData": {
+                    "typeUrl": "type.googleapis.com/google.crypto.tink.XChaCha20Poly1305Key",
+                    "value": "CAAaIDAxMjM0NTY3ODlhYmNkZWZnaGlqa2xtbm9wcXJzdHV2",
+                    "keyMaterialType": "SYMMETRIC"
+                },
+                "status": "ENABLED",
+                "keyId": 1001,
+                "outputPrefixType": "TINK"
+            },
+            {
+                "keyData": {
+                    "typeUrl": "type.googleapis.com/google.crypto.tink.AesGcmKey",
+                    "value": "CAAaIHRpd1FDV0tDc1cxZDZxelpmcDdIWXZuUnFaUFlZaE10",
+                    "keyMaterialType": "SYMMETRIC"
+                },
+                "status": "ENABLED",
+                "keyId": 2002,
+                "outputPrefixType": "TINK"
+            },
+            {
+                "keyData": {
+                    "typeUrl": "type.googleapis.com/google.crypto.tink.XChaCha20Poly1305Key",
+                    "value": "CAAaIHV1T3hmcFdnUmdJRW8zZElyZG8waG5ISkhGMWhudHZX",
+                    "keyMaterialType": "SYMMETRIC"
+                },
+                "status": "DISABLED",
+                "keyId": 3003,
+                "outputPrefixType": "TINK"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn imports_enabled_keys() {
+        let keyring = Keyring::from_tink_json(KEYSET).unwrap();
+        assert_eq!(keyring.primary_id(), "2002");
+
+        let keys = keyring.keys();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].expose_secret(), b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt");
+        assert_eq!(keys[1].expose_secret(), b"0123456789abcdefghijklmnopqrstuv");
+        assert!(keyring.key("3003").is_none());
+    }
+
+    #[test]
+    fn test_invalid_keysets() {
+        // Test that AES-128-GCM keys are rejected.
+        let keyset = KEYSET.replace("CAAaIDAxMjM0NTY3ODlhYmNkZWZnaGlqa2xtbm9wcXJzdHV2", "CAAaEDAxMjM0NTY3ODlhYmNkZWY=");
+        assert!(matches!(
+            Keyring::from_tink_json(&keyset).unwrap_err(),
+            KeyringError::UnsupportedTinkKey { id, type_url } if id == "1001" && type_url.ends_with("XChaCha20Poly1305Key"),
+        ));
+
+        // Test that the primary key must be enabled.
+        let keyset = KEYSET.replace(r#""primaryKeyId": 2002"#, r#""primaryKeyId": 3003"#);
+        assert!(matches!(Keyring::from_tink_json(&keyset).unwrap_err(), KeyringError::MissingPrimaryKey { id } if id == "3003"));
+    }
+
+    #[test]
+    fn reads_key_value() {
+        // Test that other fields are skipped, & that truncated keys are rejected.
+        assert_eq!(key_value(&[0x08, 0x01, 0x12, 0x00, 0x1a, 0x02, 0xab, 0xcd]), Some([0xab, 0xcd].as_slice()));
+        assert_eq!(key_value(&[0x08, 0x01, 0x1a, 0x20, 0xab]), None);
+    }
+}
@@ -6,6 +6,7 @@
 //!
 //! The first key provided is considered the primary key, & is always used to encrypt new payloads.
 //! The following keys are used in the order provided when the primary key can't decrypt a payload. This allows you to rotate keys.
+//! To separate duties, [`Config::encrypt_key`] & [`Config::decrypt_keys`] can be overridden to use different keys for each operation.
 //!
 //! ```
 //! use encrypted_message::{
@@ -38,14 +39,30 @@
 //! openssl rand -hex 32
 //! ```
 //!
+//! Or using the [`key_generation`] module:
+//! ```
+//! use encrypted_message::{config::ExposeSecret as _, key_generation};
+//!
+//! let hex_key = key_generation::generate_key_hex();
+//! assert_eq!(hex_key.expose_secret().len(), 64);
+//! ```
+//!
+//! If you're provided keys that aren't 32 bytes long, you can normalize them with [`key_generation::normalize_key`],
+//! or return them from [`Config::keys`] with [`key_generation::normalize_keys`].
+//!
+//! To manage keys & their rotation, you can load them from a [`Keyring`](keyring::Keyring), a JSON document
+//! mapping key ids to keys with a designated primary key, & return [`Keyring::keys`](keyring::Keyring::keys) from [`Config::keys`].
+//!
 //! ## Encryption strategies
 //!
-//! Two encryption strategies are provided, [`Deterministic`](crate::strategy::Deterministic) & [`Randomized`](crate::strategy::Randomized).
+//! Three encryption strategies are provided, [`Deterministic`](crate::strategy::Deterministic), [`Randomized`](crate::strategy::Randomized) & [`Counter`](crate::strategy::Counter).
 //!
 //! - [`Deterministic`](crate::strategy::Deterministic) encryption will always produce the same encrypted message for the same payload, allowing you to query encrypted data.
 //! - [`Randomized`](crate::strategy::Randomized) encryption will always produce a different encrypted message for the same payload. More secure than [`Deterministic`](crate::strategy::Deterministic), but impossible to query without decrypting all data.
+//! - [`Counter`](crate::strategy::Counter) encryption (requires the `std` feature) behaves like [`Randomized`](crate::strategy::Randomized), but guarantees nonces are unique within a process run by combining a random prefix with a monotonic counter.
 //!
-//! It's recommended to use different keys for each encryption strategy.
+//! It's recommended to use different keys for each encryption strategy, which you can check with [`Config::assert_key_separation`].
+//! To use a single master key, derive a key for each strategy with [`derive_strategy_key`](key_generation::derive_strategy_key).
 //!
 //! ## Defining encrypted fields
 //!
@@ -72,6 +89,34 @@
 //! }
 //! ```
 //!
+//! To avoid repeating the configuration type across large models, define a type alias next to your configuration:
+//!
+//! ```
+//! # use encrypted_message::{config::{Config, Secret}, strategy::Randomized};
+//! #
+//! # #[derive(Debug, Default)]
+//! # struct EncryptionConfig;
+//! # impl Config for EncryptionConfig {
+//! #     type Strategy = Randomized;
+//! #
+//! #     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+//! #         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+//! #     }
+//! # }
+//! #
+//! use encrypted_message::EncryptedMessage;
+//!
+//! type Encrypted<P> = EncryptedMessage<P, EncryptionConfig>;
+//!
+//! struct User {
+//!     diary: Encrypted<String>,
+//!     lucky_numbers: Encrypted<Vec<u32>>,
+//! }
+//! ```
+//!
+//! With the `derive` feature enabled, you can derive `EncryptedFields` to generate `encrypt_<field>` & `decrypt_<field>`
+//! methods for the fields marked with `#[encrypted]`, optionally with a configuration expression, e.g. `#[encrypted(config = self.config)]`.
+//!
 //! ## Encrypting & decrypting payloads
 //!
 //! If your [`Config`] implements the [`Default`] trait (like above), you can use the shorthand methods:
@@ -110,6 +155,7 @@
 //! use encrypted_message::{
 //!     EncryptedMessage,
 //!     config::{Config, Secret, ExposeSecret as _},
+//!     key_generation,
 //!     strategy::Randomized,
 //! };
 //! use pbkdf2::pbkdf2_hmac_array;
@@ -127,7 +173,8 @@
 //!     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
 //!         let raw_key = self.user_password.expose_secret().as_bytes();
 //!         let salt = self.salt.expose_secret().as_bytes();
-//!         vec![pbkdf2_hmac_array::<Sha256, 32>(raw_key, salt, 2_u32.pow(16)).into()]
+//!         let mut key = pbkdf2_hmac_array::<Sha256, 32>(raw_key, salt, 2_u32.pow(16));
+//!         vec![key_generation::into_secret(&mut key)]
 //!     }
 //! }
 //!
@@ -149,38 +196,144 @@
 //! // Decrypt the user's diary.
 //! let decrypted: String = user.diary.decrypt_with_config(&config).unwrap();
 //! ```
+//!
+//! ## Packing multiple values
+//!
+//! Values that are always read & written together can be packed into a single [`EncryptedMessage`],
+//! using a tuple or a struct as the payload type. Every message stores its own nonce & tag,
+//! so packing three values saves two of each (around 100 bytes in the JSON representation).
+//! The payload is encrypted as a whole, so it's always decrypted as a whole too.
+//!
+//! ```
+//! # use encrypted_message::{EncryptedMessage, config::{Config, Secret}, strategy::Randomized};
+//! #
+//! # #[derive(Debug, Default)]
+//! # struct EncryptionConfig;
+//! # impl Config for EncryptionConfig {
+//! #     type Strategy = Randomized;
+//! #
+//! #     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+//! #         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+//! #     }
+//! # }
+//! #
+//! // Encrypt a set of credentials.
+//! let credentials: EncryptedMessage<(String, String, u16), EncryptionConfig> = EncryptedMessage::encrypt((
+//!     "rigo".to_string(),
+//!     "very-secret-password".to_string(),
+//!     5432,
+//! )).unwrap();
+//!
+//! // Decrypt the credentials.
+//! let (username, password, port) = credentials.decrypt().unwrap();
+//! ```
+//!
+//! ## `no_std` support
+//!
+//! The crate is `no_std` compatible (it still requires `alloc`) when its default `std` feature is disabled.
+//! Random nonces are then generated using [`getrandom`](https://docs.rs/getrandom), which must support your target.
+//! Features depending on `std`, like the [`Counter`](crate::strategy::Counter) strategy & the Diesel integration, are unavailable.
+//!
+//! ```toml
+//! [dependencies]
+//! encrypted-message = { version = "0.3", default-features = false }
+//! ```
+//!
+//! ## WebAssembly support
+//!
+//! Random nonces are generated using the operating system's random number generator, which isn't available
+//! in `wasm32-unknown-unknown`. Enable the `js` feature to use the Web Crypto API instead, in browsers & Node.js.
+//!
+//! ```toml
+//! [dependencies]
+//! encrypted-message = { version = "0.3", features = ["js"] }
+//! ```
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 pub mod strategy;
-use strategy::Strategy;
+use strategy::{Deterministic, Strategy, StrategyKind};
 
 pub mod error;
-pub use error::{EncryptionError, DecryptionError};
+pub use error::{EncryptionError, DecryptionError, ConfigError, ValidationError, KeyringError};
 
 mod integrations;
 
 pub mod config;
 use config::Config;
 
+pub mod serialization;
+use serialization::Serialization;
+
+pub mod key_generation;
+
+pub mod keyring;
+
+pub mod diagnostics;
+
+pub mod raw;
+
+pub mod dynamic;
+
+mod batch;
+pub use batch::{rotate, verify_all, DecryptIter, DecryptAll};
+#[cfg(feature = "std")]
+pub use batch::decrypt_stream_json;
+
+mod flat;
+pub use flat::FlatEncryptedMessage;
+
+#[cfg(feature = "derive")]
+pub use encrypted_message_derive::EncryptedFields;
+
+#[cfg(feature = "bincode")]
+mod binary;
+
 mod utilities;
 use utilities::base64;
 
+#[cfg(any(test, fuzzing))]
+pub mod fuzzing;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod fixtures;
+
 #[cfg(test)]
 mod testing;
 
-use std::{fmt::Debug, marker::PhantomData};
+use alloc::{borrow::Cow, collections::BTreeMap, string::{String, ToString as _}, vec::Vec};
+use core::{
+    borrow::Borrow,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    num::NonZeroUsize,
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::value::RawValue;
 use chacha20poly1305::{KeyInit as _, XChaCha20Poly1305, AeadInPlace as _};
-use secrecy::ExposeSecret as _;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq as _;
+use secrecy::{ExposeSecret as _, Secret, SecretString};
+pub use zeroize::{Zeroize, Zeroizing};
 
 /// Used to safely handle & transport encrypted data within your application.
 /// It contains an encrypted payload, along with a nonce & tag that are
 /// used in the encryption & decryption processes.
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+///
+/// With the `redact-debug` feature enabled, its [`Debug`] representation only contains the length
+/// of the encrypted payload, e.g. `EncryptedMessage { payload: <redacted 32 bytes>, .. }`.
+#[derive(Deserialize, Serialize, PartialEq, Eq)]
 #[cfg_attr(feature = "diesel", derive(diesel::AsExpression, diesel::FromSqlRow))]
 #[cfg_attr(feature = "diesel", diesel(sql_type = diesel::sql_types::Json))]
 #[cfg_attr(all(feature = "diesel", feature = "diesel-postgres"), diesel(sql_type = diesel::sql_types::Jsonb))]
-pub struct EncryptedMessage<P: Debug + DeserializeOwned + Serialize, C: Config> {
+#[cfg_attr(all(feature = "diesel", feature = "diesel-sqlite"), diesel(sql_type = diesel::sql_types::Text))]
+#[cfg_attr(feature = "diesel-binary", diesel(sql_type = diesel::sql_types::Binary))]
+pub struct EncryptedMessage<P, C: Config> {
     /// The base64-encoded & encrypted payload.
     #[serde(rename = "p")]
     payload: String,
@@ -190,221 +343,2620 @@ pub struct EncryptedMessage<P: Debug + DeserializeOwned + Serialize, C: Config>
     headers: EncryptedMessageHeaders,
 
     /// The payload type.
+    /// Uses a function pointer, as no payload is stored, so the message is always `Send` & `Sync`.
     #[serde(skip)]
-    payload_type: PhantomData<P>,
+    payload_type: PhantomData<fn() -> P>,
 
     /// The configuration for the encrypted message.
     #[serde(skip)]
-    config: PhantomData<C>,
+    config: PhantomData<fn() -> C>,
+}
+
+/// The latest version of the message format, which new configurations should write,
+/// see [`Config::format_version`] & [`EncryptedMessage::format_version`].
+pub const FORMAT_VERSION: u8 = 2;
+
+/// A [JSON Schema](https://json-schema.org) describing the JSON representation of an [`EncryptedMessage`],
+/// for tooling & for reading messages from other languages.
+pub const FORMAT: &str = include_str!("../format.schema.json");
+
+/// A payload of either of two types, returned by [`EncryptedMessage::decrypt_either_with_config`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Either<L, R> {
+    /// The payload was deserialized into the payload type of the message.
+    Left(L),
+
+    /// The payload was deserialized into the fallback type.
+    Right(R),
+}
+
+/// Implemented by payloads exposing a public discriminant, e.g. the variant of an enum,
+/// see [`EncryptedMessage::encrypt_with_discriminant_and_config`].
+pub trait Discriminant {
+    /// Returns the discriminant of the payload, which is stored unencrypted in the headers.
+    fn discriminant(&self) -> String;
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// The metadata key the discriminant of the payload is stored under, see [`Discriminant`].
+pub const DISCRIMINANT_METADATA_KEY: &str = "discriminant";
+
+/// The metadata key the epoch of the message is stored under, see [`Config::epoch`].
+/// When it's set, the nonce is scoped to the epoch.
+pub const EPOCH_METADATA_KEY: &str = "epoch";
+
+/// The metadata key the expiration time of the message is stored under, in seconds since the Unix epoch,
+/// see [`Config::validity`]. Messages are only decrypted until that time.
+pub const EXPIRES_AT_METADATA_KEY: &str = "expires_at";
+
+/// The size of the nonce, in bytes.
+const NONCE_SIZE: usize = 24;
+
+/// The size of the auth tag, in bytes.
+const TAG_SIZE: usize = 16;
+
+/// The size of the key commitment, in bytes.
+const KEY_COMMITMENT_SIZE: usize = 32;
+
+/// The first version of the message format that stores a key commitment.
+const KEY_COMMITMENT_VERSION: u8 = 2;
+
+/// The size of the HMAC tag, in bytes, see [`Config::hmac_tag`].
+const HMAC_TAG_SIZE: usize = 32;
+
+/// The byte the serialization format is bound after in the associated data, see [`message_associated_data`].
+/// It can't start the associated data of JSON messages, which starts with a version, the `{` of the metadata, or nothing.
+const SERIALIZATION_ASSOCIATED_DATA_TAG: u8 = 0xff;
+
+/// The byte the block size of the padding is bound after in the associated data, see [`message_associated_data`].
+const PADDING_ASSOCIATED_DATA_TAG: u8 = 0xfe;
+
+/// The largest block size payloads can be padded to, in bytes, see [`Config::pad_to`].
+/// This also bounds the memory a stored message can make padding allocate, since the `pd` header is parsed before it's authenticated.
+const MAX_BLOCK_SIZE: usize = 1 << 16;
+
+/// The size of a wrapped data key, in bytes: the nonce, followed by the encrypted key & its auth tag,
+/// see [`EncryptedMessage::encrypt_for_recipients_with_config`].
+const WRAPPED_KEY_SIZE: usize = NONCE_SIZE + 32 + TAG_SIZE;
+
+/// The associated data data keys are wrapped with, so a wrapped key can't be mistaken for another ciphertext.
+const WRAPPED_KEY_CONTEXT: &[u8] = b"encrypted-message recipient key";
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 struct EncryptedMessageHeaders {
+    /// The version of the message format, which determines how the payload is decrypted.
+    /// Omitted for version 0, the format used before it was stored.
+    #[serde(rename = "v", default, skip_serializing_if = "EncryptedMessageHeaders::is_initial_version")]
+    version: u8,
+
     /// The base64-encoded nonce used to encrypt the payload.
     #[serde(rename = "iv")]
     nonce: String,
 
     /// The base64-encoded auth tag used to verify the encrypted payload.
-    #[serde(rename = "at")]
-    tag: String,
-}
+    /// Omitted when the tag is appended to the encrypted payload, see [`Config::combined_tag`].
+    #[serde(rename = "at", default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
 
-impl<P: Debug + DeserializeOwned + Serialize, C: Config> EncryptedMessage<P, C> {
-    /// Creates an [`EncryptedMessage`] from a payload, using the XChaCha20Poly1305 encryption cipher.
-    ///
-    /// # Errors
-    ///
-    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized into a JSON string.
-    ///   See [`serde_json::to_vec`] for more information.
-    pub fn encrypt_with_config(payload: P, config: &C) -> Result<Self, EncryptionError> {
-        let payload = serde_json::to_vec(&payload)?;
+    /// The format the payload was serialized with before encryption.
+    /// Omitted for JSON, which is the default.
+    #[serde(rename = "e", default, skip_serializing_if = "Serialization::is_json")]
+    serialization: Serialization,
 
-        let key = config.primary_key();
-        let nonce = C::Strategy::generate_nonce_for(&payload, key.expose_secret());
-        let cipher = XChaCha20Poly1305::new_from_slice(key.expose_secret()).unwrap();
+    /// The strategy the payload was encrypted with.
+    /// Omitted for messages encrypted before it was stored.
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    strategy: Option<StrategyKind>,
 
-        let mut buffer = payload;
-        let tag = cipher.encrypt_in_place_detached(&nonce.into(), b"", &mut buffer).unwrap();
+    /// The time the payload was encrypted at, in seconds since the Unix epoch.
+    /// Only stored when [`Config::stamp_time`] returns `true`.
+    #[serde(rename = "ts", default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
 
-        Ok(EncryptedMessage {
-            payload: base64::encode(buffer),
-            headers: EncryptedMessageHeaders {
-                nonce: base64::encode(nonce),
-                tag: base64::encode(tag),
-            },
-            payload_type: PhantomData,
-            config: PhantomData,
-        })
-    }
+    /// Non-secret application metadata, authenticated as associated data.
+    /// Omitted when empty.
+    #[serde(rename = "m", default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
 
-    /// Decrypts the payload of the [`EncryptedMessage`], trying all available keys in order until it finds one that works.
-    ///
-    /// # Errors
-    ///
-    /// - Returns a [`DecryptionError::Base64Decoding`] error if the base64-decoding of the payload, nonce, or tag fails.
-    /// - Returns a [`DecryptionError::Decryption`] error if the payload cannot be decrypted with any of the available keys.
-    /// - Returns a [`DecryptionError::Deserialization`] error if the payload cannot be deserialized into the expected type.
-    ///   See [`serde_json::from_slice`] for more information.
-    pub fn decrypt_with_config(&self, config: &C) -> Result<P, DecryptionError> {
-        let payload = base64::decode(&self.payload)?;
-        let nonce = base64::decode(&self.headers.nonce)?;
-        let tag = base64::decode(&self.headers.tag)?;
+    /// The block size the serialized payload was padded to before encryption.
+    /// Only stored when [`Config::pad_to`] returns a block size.
+    #[serde(rename = "pd", default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_block_size")]
+    padding: Option<NonZeroUsize>,
 
-        for key in config.keys() {
-            let cipher = XChaCha20Poly1305::new_from_slice(key.expose_secret()).unwrap();
+    /// The base64-encoded commitment to the key the payload was encrypted with, see [`key_commitment`].
+    /// Only stored from version 2 of the message format.
+    #[serde(rename = "kc", default, skip_serializing_if = "Option::is_none")]
+    key_commitment: Option<String>,
 
-            let mut buffer = payload.clone();
-            if cipher.decrypt_in_place_detached(nonce.as_slice().into(), b"", &mut buffer, tag.as_slice().into()).is_err() {
-                continue;
-            };
+    /// The base64-encoded HMAC-SHA256 tag of the encrypted payload, see [`hmac_tag`].
+    /// Only stored when [`Config::hmac_tag`] returns `true`.
+    #[serde(rename = "ht", default, skip_serializing_if = "Option::is_none")]
+    hmac_tag: Option<String>,
 
-            return Ok(serde_json::from_slice(&buffer)?);
-        }
+    /// The base64-encoded data key the payload was encrypted with, wrapped under the key of each recipient, see [`wrap_key`].
+    /// Only stored for messages encrypted with [`EncryptedMessage::encrypt_for_recipients_with_config`].
+    #[serde(rename = "rk", default, skip_serializing_if = "Vec::is_empty")]
+    recipient_keys: Vec<String>,
+}
 
-        Err(DecryptionError::Decryption)
+impl EncryptedMessageHeaders {
+    /// The names of the headers in the JSON representation, which strict parsing accepts, see [`EncryptedMessage::parse_strict`].
+    const FIELDS: [&'static str; 11] = ["v", "iv", "at", "e", "s", "ts", "m", "pd", "kc", "ht", "rk"];
+
+    /// Returns `true` if the version is 0, the format used before the version was stored.
+    fn is_initial_version(version: &u8) -> bool {
+        *version == 0
     }
 }
 
-impl<P: Debug + DeserializeOwned + Serialize, C: Config + Default> EncryptedMessage<P, C> {
-    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_config`],
-    /// passing `&C::default()` as the configuration.
-    pub fn encrypt(payload: P) -> Result<Self, EncryptionError> {
-        Self::encrypt_with_config(payload, &C::default())
+/// Deserializes the block size of the padding, rejecting block sizes larger than [`MAX_BLOCK_SIZE`].
+fn deserialize_block_size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<NonZeroUsize>, D::Error> {
+    match Option::<NonZeroUsize>::deserialize(deserializer)? {
+        Some(block_size) if block_size.get() > MAX_BLOCK_SIZE => {
+            Err(serde::de::Error::invalid_value(serde::de::Unexpected::Unsigned(block_size.get() as u64), &"a block size of at most 65536 bytes"))
+        },
+        block_size => Ok(block_size),
     }
+}
 
-    /// This method is a shorthand for [`EncryptedMessage::decrypt_with_config`],
-    /// passing `&C::default()` as the configuration.
-    pub fn decrypt(&self) -> Result<P, DecryptionError> {
-        self.decrypt_with_config(&C::default())
+/// Returns an error for the first key of the object that isn't one of the provided fields.
+/// Other values are left to deserialization.
+fn deny_unknown_fields(value: &serde_json::Value, fields: &'static [&'static str]) -> Result<(), serde_json::Error> {
+    let unknown_field = value.as_object().and_then(|object| object.keys().find(|key| !fields.contains(&key.as_str())));
+
+    match unknown_field {
+        Some(key) => Err(serde::de::Error::unknown_field(key, fields)),
+        None => Ok(()),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Returns the associated data authenticated along with the payload, the JSON representation of the metadata.
+/// Messages without metadata have no associated data.
+fn associated_data(metadata: &BTreeMap<String, String>) -> Vec<u8> {
+    if metadata.is_empty() {
+        return Vec::new();
+    }
 
-    use serde_json::json;
+    // A `BTreeMap` is ordered by key, so its JSON representation is always the same.
+    serde_json::to_vec(metadata).unwrap()
+}
 
-    use crate::testing::{TestConfigDeterministic, TestConfigRandomized};
+/// Returns the associated data authenticated along with the payload of a message of the provided version.
+///
+/// From version 2, the version & the key commitment precede the metadata, so they can't be stripped
+/// to downgrade the message to a version without a key commitment.
+///
+/// Formats other than JSON are bound first, after [`SERIALIZATION_ASSOCIATED_DATA_TAG`], so the `e` header can't be changed
+/// to deserialize the payload with another format. The block size of padded payloads follows, after [`PADDING_ASSOCIATED_DATA_TAG`],
+/// so the `pd` header can't be added or removed to change how the payload is unpadded. Unpadded JSON messages keep
+/// the associated data they had before these were bound.
+fn message_associated_data(
+    version: u8,
+    key_commitment: Option<&[u8]>,
+    serialization: Serialization,
+    padding: Option<NonZeroUsize>,
+    metadata: &BTreeMap<String, String>,
+) -> Vec<u8> {
+    let mut message_associated_data = Vec::new();
+    if !serialization.is_json() {
+        message_associated_data.extend_from_slice(&[SERIALIZATION_ASSOCIATED_DATA_TAG, serialization.id()]);
+    }
 
-    mod encrypt {
-        use super::*;
+    if let Some(block_size) = padding {
+        message_associated_data.push(PADDING_ASSOCIATED_DATA_TAG);
+        message_associated_data.extend_from_slice(&(block_size.get() as u64).to_be_bytes());
+    }
 
-        #[test]
-        fn deterministic() {
-            assert_eq!(
-                EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap(),
-                EncryptedMessage {
-                    payload: "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF".to_string(),
-                    headers: EncryptedMessageHeaders {
-                        nonce: "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0".to_string(),
-                        tag: "uXQhmffPV/1D7qG8stw6vA==".to_string(),
-                    },
-                    payload_type: PhantomData,
-                    config: PhantomData,
-                },
-            );
-        }
+    if version >= KEY_COMMITMENT_VERSION {
+        message_associated_data.push(version);
+        message_associated_data.extend_from_slice(key_commitment.unwrap_or_default());
+    }
 
-        #[test]
-        fn randomized() {
-            let payload = "much secret much secure".to_string();
+    message_associated_data.extend_from_slice(&associated_data(metadata));
 
-            // Test that the encrypted messages never match, even when they contain the same payload.
-            assert_ne!(
-                EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload.clone()).unwrap(),
-                EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload).unwrap(),
-            );
-        }
+    message_associated_data
+}
 
-        #[test]
-        fn test_serialization_error() {
-            // A map with non-string keys can't be serialized into JSON.
-            let map = std::collections::HashMap::<[u8; 2], String>::from([([1, 2], "Hi".to_string())]);
-            assert!(matches!(EncryptedMessage::<_, TestConfigDeterministic>::encrypt(map).unwrap_err(), EncryptionError::Serialization(_)));
-        }
+impl<P: Debug + DeserializeOwned + Serialize, C: Config> EncryptedMessage<P, C> {
+    /// Creates an [`EncryptedMessage`] from a payload, using the XChaCha20Poly1305 encryption cipher.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_config(payload: P, config: &C) -> Result<Self, EncryptionError> {
+        Self::encrypt_ref_with_config(&payload, config)
     }
 
-    mod decrypt {
-        use super::*;
+    /// Creates an [`EncryptedMessage`] from a borrowed payload, using the XChaCha20Poly1305 encryption cipher.
+    ///
+    /// This allows encrypting a borrowed form of the payload type without allocating an owned value first,
+    /// like a `&str` for an [`EncryptedMessage<String, C>`](EncryptedMessage).
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_ref_with_config<Q: Serialize + ?Sized>(payload: &Q, config: &C) -> Result<Self, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        let settings = EncryptionSettings::from_config(config)?;
+        let payload = settings.serialize(payload)?;
 
-        #[test]
-        fn decrypts_correctly() {
-            let payload = "hi :D".to_string();
-            let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt(payload.clone()).unwrap();
-            assert_eq!(message.decrypt().unwrap(), payload);
-        }
+        Ok(Self::encrypt_bytes(payload, &settings, &config.encrypt_key()))
+    }
 
-        #[test]
-        fn test_base64_decoding_error() {
-            fn generate() -> EncryptedMessage<String, TestConfigDeterministic> {
-                EncryptedMessage::encrypt("hi :)".to_string()).unwrap()
-            }
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// using the key at the provided index of [`Config::keys`] instead of the primary key.
+    ///
+    /// This is useful for staged rollouts, e.g. encrypting with the next key on some nodes before making it the primary key
+    /// everywhere. Messages are decrypted with all keys, so they can be read by any node that has the key.
+    /// Note that [`rotate`] re-encrypts messages that weren't encrypted with the primary key.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::KeyIndexOutOfRange`] error if there's no key at the provided index.
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_config_using_key(payload: P, config: &C, key_index: usize) -> Result<Self, EncryptionError> {
+        let keys = config.keys();
+        let key = keys.get(key_index).ok_or(EncryptionError::KeyIndexOutOfRange { index: key_index, len: keys.len() })?;
 
-            // Test invalid payload.
-            let mut message = generate();
-            message.payload = "invalid".to_string();
-            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+        let settings = EncryptionSettings::from_config(config)?;
+        let payload = settings.serialize(&payload)?;
 
-            // Test invalid nonce.
-            let mut message = generate();
-            message.headers.nonce = "invalid".to_string();
-            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+        Ok(Self::encrypt_bytes(payload, &settings, key))
+    }
 
-            // Test invalid tag.
-            let mut message = generate();
-            message.headers.tag = "invalid".to_string();
-            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// decryptable with any of the provided recipient keys, e.g. a user's key & an admin recovery key.
+    ///
+    /// The payload is encrypted once, with a random data key, which is stored in the headers wrapped under each recipient key.
+    /// Messages are decrypted as usual, with a configuration that has one of the recipient keys. As the data key is random,
+    /// the [`Randomized`](strategy::Randomized) strategy is always used, regardless of [`Config::Strategy`].
+    ///
+    /// **Any recipient can decrypt the data key**, so any recipient can also change the payload for the other recipients.
+    /// [`rotate`] leaves these messages untouched, as re-encrypting them with a single key would drop the other recipients.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::NoRecipients`] error if no recipient keys are provided.
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_for_recipients_with_config(payload: P, recipient_keys: &[Secret<[u8; 32]>], config: &C) -> Result<Self, EncryptionError> {
+        if recipient_keys.is_empty() {
+            return Err(EncryptionError::NoRecipients);
         }
 
-        #[test]
-        fn test_decryption_error() {
-            // Created using a random disposed key not used in other tests.
-            let message = EncryptedMessage {
-                payload: "c+cOk5DA9y/4LulYA+WCAxFjI8WGbTVK".to_string(),
-                headers: EncryptedMessageHeaders {
-                    nonce: "dBI9t1Y8mUBea+b0nyWXlTeoCdrNPLkg".to_string(),
-                    tag: "6NLYKDiHNRkpwoQusf9BaA==".to_string(),
-                },
-                payload_type: PhantomData::<String>,
-                config: PhantomData::<TestConfigDeterministic>,
-            };
+        let settings = EncryptionSettings::from_config(config)?;
+        let payload = settings.serialize(&payload)?;
 
-            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
-        }
+        let mut data_key = utilities::random::random();
+        let data_key = key_generation::into_secret(&mut data_key);
+        let cipher = XChaCha20Poly1305::new(data_key.expose_secret().into());
 
-        #[test]
-        fn test_deserialization_error() {
-            let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        let mut message = Self::encrypt_bytes_with_strategy(payload, &settings, &data_key, &cipher, StrategyKind::Randomized, |_| utilities::random::random());
+        message.headers.recipient_keys = recipient_keys.iter().map(|key| wrap_key(key, &data_key)).collect();
 
-            // Change the payload type to an integer, even though the initial payload was serialized as a string.
-            let message = EncryptedMessage {
-                payload: message.payload,
-                headers: message.headers,
-                payload_type: PhantomData::<u8>,
-                config: message.config,
-            };
+        Ok(message)
+    }
 
-            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Deserialization(_)));
-        }
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// attaching the provided metadata to it.
+    ///
+    /// The metadata is stored in the headers, so it's readable without the key, see [`EncryptedMessage::metadata`].
+    /// It's authenticated along with the payload, so the message fails to decrypt if the metadata is tampered with.
+    /// **It isn't encrypted**, so it must not contain secrets.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_metadata_and_config(payload: P, metadata: BTreeMap<String, String>, config: &C) -> Result<Self, EncryptionError> {
+        let mut settings = EncryptionSettings::from_config(config)?;
+        settings.metadata.extend(metadata);
+        let payload = settings.serialize(&payload)?;
+
+        Ok(Self::encrypt_bytes(payload, &settings, &config.encrypt_key()))
     }
 
-    #[test]
-    fn allows_rotating_keys() {
-        // Created using TestConfig's second key.
-        let message = EncryptedMessage {
-            payload: "LC4u257NQw==".to_string(),
-            headers: EncryptedMessageHeaders {
-                nonce: "nv6rH50Sn2Po320KT57fg1a3Lyu/IGeG".to_string(),
-                tag: "/jK8Y7fOyA+S7/dTxRR3SQ==".to_string(),
-            },
-            payload_type: PhantomData::<String>,
-            config: PhantomData::<TestConfigDeterministic>,
-        };
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// storing its [`Discriminant`] in the metadata, under the [`DISCRIMINANT_METADATA_KEY`] key.
+    ///
+    /// This allows routing messages, e.g. on an encrypted queue, by reading [`EncryptedMessage::discriminant`]
+    /// without decrypting them. Like the rest of the metadata, the discriminant is authenticated, but **isn't encrypted**.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_discriminant_and_config(payload: P, config: &C) -> Result<Self, EncryptionError>
+    where
+        P: Discriminant,
+    {
+        let metadata = BTreeMap::from([(String::from(DISCRIMINANT_METADATA_KEY), payload.discriminant())]);
 
-        // Ensure that if encrypting the same value, it'll be different since it'll use the new primary key.
-        // Note that we're using the `Deterministic` encryption strategy, so the encrypted message would be the
-        // same if the key was the same.
+        Self::encrypt_with_metadata_and_config(payload, metadata, config)
+    }
+
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// in the provided epoch instead of the current one, see [`Config::epoch`].
+    ///
+    /// With the [`Deterministic`] strategy, this allows querying messages encrypted in a past epoch.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_in_epoch_with_config(payload: P, epoch: &str, config: &C) -> Result<Self, EncryptionError> {
+        let metadata = BTreeMap::from([(String::from(EPOCH_METADATA_KEY), String::from(epoch))]);
+
+        Self::encrypt_with_metadata_and_config(payload, metadata, config)
+    }
+
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// scoping the nonce to the provided context in addition to [`Config::context`].
+    ///
+    /// With the [`Deterministic`] strategy, the same payload only produces the same encrypted message
+    /// within the same context. This allows deduplicating data within a window, e.g. by using the date as context,
+    /// without making it comparable globally. The context isn't stored, & isn't needed for decryption.
+    /// Other strategies ignore the context.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_context_and_config(payload: P, context: &[u8], config: &C) -> Result<Self, EncryptionError> {
+        let settings = EncryptionSettings { scope: context, ..EncryptionSettings::from_config(config)? };
+        let payload = settings.serialize(&payload)?;
+
+        Ok(Self::encrypt_bytes(payload, &settings, &config.encrypt_key()))
+    }
+
+    /// Creates an [`EncryptedMessage`] from a payload like [`EncryptedMessage::encrypt_with_config`],
+    /// generating the nonce with the provided strategy instead of the [`Config::Strategy`].
+    ///
+    /// This allows a single column to hold both [`Deterministic`] messages, e.g. for lookup keys, & randomized ones,
+    /// without defining two configurations. The strategy is stored in the headers, see [`EncryptedMessage::encoded_strategy`],
+    /// & decryption doesn't depend on it. Note that [`rotate`] re-encrypts messages with the [`Config::Strategy`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the serialized payload exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the payload is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_with_config_as<S: Strategy>(payload: P, config: &C) -> Result<Self, EncryptionError> {
+        let settings = EncryptionSettings::from_config(config)?;
+        let payload = settings.serialize(&payload)?;
+
+        let key = config.encrypt_key();
+        let cipher = XChaCha20Poly1305::new(key.expose_secret().into());
+
+        Ok(Self::encrypt_bytes_with_strategy(payload, &settings, &key, &cipher, S::KIND, |buffer| {
+            S::generate_nonce_for_version(settings.version, buffer, key.expose_secret(), &settings.nonce_context())
+        }))
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`], trying all available keys in order until it finds one that works.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`DecryptionError::UnsupportedVersion`] error if the message format version is not supported.
+    /// - Returns a [`DecryptionError::Base64Decoding`] error if the base64-decoding of the payload, nonce, or tag fails.
+    /// - Returns a [`DecryptionError::Decryption`] error if the payload cannot be decrypted with any of the available keys.
+    /// - Returns a [`DecryptionError::Deserialization`] error if the payload cannot be deserialized into the expected type
+    ///   with the [`Serialization`] it was serialized with.
+    pub fn decrypt_with_config(&self, config: &C) -> Result<P, DecryptionError> {
+        let (_, payload) = self.decrypt_bytes(config)?;
+
+        self.headers.serialization.deserialize(&payload)
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`] like [`EncryptedMessage::decrypt_with_config`],
+    /// falling back to the keys of another configuration if none of the keys of the configuration can decrypt it.
+    ///
+    /// This is useful when migrating between configurations with different key sources. The fallback configuration
+    /// is only asked for its keys when needed, & only its keys are used, as decryption doesn't depend on the strategy.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_with_fallback_config<F: Config>(&self, config: &C, fallback: &F) -> Result<P, DecryptionError> {
+        let mut payload = Zeroizing::new(Vec::new());
+        match self.decrypt_in_place(config, &mut payload) {
+            Err(DecryptionError::Decryption) => {
+                let result = self.decrypt_in_place_with_ciphers(&ciphers_for(&fallback.decrypt_keys()), DecryptionSettings::from_config(fallback), &mut payload);
+                fallback.on_decrypt(result.is_ok());
+                result?;
+            },
+            result => {
+                result?;
+            },
+        }
+
+        self.headers.serialization.deserialize(&payload)
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`] like [`EncryptedMessage::decrypt_with_config`],
+    /// but uses the provided scratch buffer for the decoded & decrypted payload instead of allocating a new one.
+    ///
+    /// This is useful when decrypting many messages in a hot path, as the same buffer can be reused
+    /// across calls. The buffer is always zeroized before returning, its capacity is kept.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_into_with_config(&self, config: &C, scratch: &mut Vec<u8>) -> Result<P, DecryptionError> {
+        let result = self.decrypt_in_place(config, scratch).and_then(|_| self.headers.serialization.deserialize(scratch));
+        scratch.zeroize();
+
+        result
+    }
+
+    /// Decrypts the payload like [`EncryptedMessage::decrypt_with_config`], returning it wrapped in [`Zeroizing`],
+    /// so the plaintext is wiped from memory when it's dropped.
+    ///
+    /// The payload type must implement [`Zeroize`], which is implemented for strings, vectors & primitives,
+    /// & can be derived for your own types with the `zeroize` crate. For types that can't implement it,
+    /// the decrypted payload lingers in memory until it's overwritten. In both cases, copies made
+    /// by the deserializer or the caller aren't zeroized.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_zeroizing_with_config(&self, config: &C) -> Result<Zeroizing<P>, DecryptionError>
+    where
+        P: Zeroize,
+    {
+        self.decrypt_with_config(config).map(Zeroizing::new)
+    }
+
+    /// Decrypts the payload like [`EncryptedMessage::decrypt_with_config`], then deserializes it into the payload type,
+    /// or into the fallback type `Q` if that fails.
+    ///
+    /// This is useful for columns containing payloads of different types, e.g. after a migration,
+    /// as the payload is only decrypted once.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`]. If the payload can't be deserialized into either type,
+    /// the [`DecryptionError::Deserialization`] error of the payload type is returned.
+    pub fn decrypt_either_with_config<Q: DeserializeOwned>(&self, config: &C) -> Result<Either<P, Q>, DecryptionError> {
+        let (_, payload) = self.decrypt_bytes(config)?;
+
+        match self.headers.serialization.deserialize(&payload) {
+            Ok(payload) => Ok(Either::Left(payload)),
+            Err(error) => self.headers.serialization.deserialize(&payload).map(Either::Right).map_err(|_| error),
+        }
+    }
+
+    /// Creates an [`EncryptedMessage`] from a payload with the provided key, instead of the keys of a configuration.
+    ///
+    /// The nonce is still generated with the [`Config::Strategy`], but the other methods of the [`Config`] aren't used:
+    /// the payload is encrypted with their default settings, e.g. serialized as JSON. This is useful for recovery & CLI tools.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized as JSON.
+    pub fn encrypt_with_key(payload: P, key: &Secret<[u8; 32]>) -> Result<Self, EncryptionError> {
+        let settings = EncryptionSettings::defaults();
+        let payload = settings.serialize(&payload)?;
+
+        Ok(Self::encrypt_bytes(payload, &settings, key))
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`] with the provided key, instead of the keys of a configuration.
+    ///
+    /// This is useful for recovery & CLI tools, see [`EncryptedMessage::encrypt_with_key`].
+    /// Without a configuration, messages of all versions are accepted, see [`Config::min_format_version`].
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_with_key(&self, key: &Secret<[u8; 32]>) -> Result<P, DecryptionError> {
+        let mut payload = Zeroizing::new(Vec::new());
+        self.decrypt_in_place_with_ciphers(&ciphers_for(core::slice::from_ref(key)), DecryptionSettings::default(), &mut payload)?;
+
+        self.headers.serialization.deserialize(&payload)
+    }
+
+    /// Creates an [`EncryptedMessage`] from each payload, like [`EncryptedMessage::encrypt_with_config`].
+    ///
+    /// The encryption key is only retrieved once, & its cipher is reused for all payloads,
+    /// which is useful for bulk inserts. A result is returned for each payload, in order.
+    ///
+    /// # Errors
+    ///
+    /// Each result is an error if the payload couldn't be encrypted. See [`EncryptedMessage::encrypt_with_config`] for more information.
+    pub fn encrypt_many_with_config(payloads: Vec<P>, config: &C) -> Vec<Result<Self, EncryptionError>> {
+        let key = config.encrypt_key();
+        let cipher = XChaCha20Poly1305::new(key.expose_secret().into());
+        let settings = EncryptionSettings::from_config(config);
+
+        payloads
+            .iter()
+            .map(|payload| {
+                let settings = settings.as_ref().map_err(Clone::clone)?;
+                let payload = settings.serialize(payload)?;
+
+                Ok(Self::encrypt_bytes_with_cipher(payload, settings, &key, &cipher))
+            })
+            .collect()
+    }
+
+    /// Decrypts the payload of each [`EncryptedMessage`], like [`EncryptedMessage::decrypt_with_config`].
+    ///
+    /// The keys are only retrieved once, & their ciphers are reused for all messages.
+    /// A result is returned for each message, in order.
+    ///
+    /// # Errors
+    ///
+    /// Each result is an error if the message couldn't be decrypted. See [`EncryptedMessage::decrypt_with_config`] for more information.
+    pub fn decrypt_many_with_config(messages: &[Self], config: &C) -> Vec<Result<P, DecryptionError>> {
+        let ciphers = ciphers_for(&config.decrypt_keys());
+        let settings = DecryptionSettings::from_config(config);
+        let mut buffer = Zeroizing::new(Vec::new());
+
+        messages
+            .iter()
+            .map(|message| {
+                let result = message.decrypt_in_place_with_ciphers(&ciphers, settings, &mut buffer);
+                config.on_decrypt(result.is_ok());
+                result?;
+
+                message.headers.serialization.deserialize(&buffer)
+            })
+            .collect()
+    }
+
+    /// Computes a blind index of the payload, a base64-encoded HMAC-SHA256 of the serialized payload
+    /// under the key returned by [`Config::index_key`].
+    ///
+    /// Storing the blind index next to a message encrypted with the [`Randomized`](strategy::Randomized) strategy
+    /// allows querying it by equality, by computing the blind index of the payload you're querying for.
+    /// Like the [`Deterministic`](strategy::Deterministic) strategy, it reveals which messages have the same payload.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the configured [`Serialization`].
+    pub fn blind_index_with_config<Q: Serialize + ?Sized>(payload: &Q, config: &C) -> Result<String, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        let payload = Zeroizing::new(config.serialization().serialize(payload)?);
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(config.index_key().expose_secret()).unwrap();
+        mac.update(&payload);
+
+        Ok(base64::encode(mac.finalize().into_bytes()))
+    }
+}
+
+// These methods only handle the encrypted form of the message, so they don't require the payload type
+// to be (de)serializable, allowing layers that only pass messages through to handle any payload type.
+impl<P, C: Config> EncryptedMessage<P, C> {
+    /// Verifies that the payload of the [`EncryptedMessage`] can be decrypted & authenticated with any of the available keys,
+    /// without deserializing it. The decrypted payload is zeroized & discarded.
+    ///
+    /// This is useful for integrity checks, where the plaintext isn't needed.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`DecryptionError::UnsupportedVersion`] error if the message format version is not supported.
+    /// - Returns a [`DecryptionError::Base64Decoding`] error if the base64-decoding of the payload, nonce, or tag fails.
+    /// - Returns a [`DecryptionError::Decryption`] error if the payload cannot be decrypted with any of the available keys.
+    pub fn verify_with_config(&self, config: &C) -> Result<(), DecryptionError> {
+        self.decrypt_bytes(config).map(|_| ())
+    }
+
+    /// Decrypts the payload of the [`EncryptedMessage`], & calls the provided closure with the serialized payload,
+    /// which is zeroized once the closure returns.
+    ///
+    /// This allows zero-copy parsing, e.g. deserializing a type borrowing from the payload like `Cow<'a, str>`,
+    /// without the plaintext escaping the closure. The payload is serialized with the [`Serialization`]
+    /// it was encrypted with, e.g. JSON by default.
+    ///
+    /// ```
+    /// # use encrypted_message::{EncryptedMessage, config::{Config, Secret}, strategy::Randomized};
+    /// #
+    /// # #[derive(Debug, Default)]
+    /// # struct UserConfig;
+    /// # impl Config for UserConfig {
+    /// #     type Strategy = Randomized;
+    /// #
+    /// #     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+    /// #         vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    /// #     }
+    /// # }
+    /// #
+    /// let message = EncryptedMessage::<String, UserConfig>::encrypt("rigo does pretty codes".to_string()).unwrap();
+    /// let len = message.decrypt_scoped(|payload| serde_json::from_slice::<&str>(payload).unwrap().len()).unwrap();
+    /// assert_eq!(len, 22);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::verify_with_config`].
+    pub fn decrypt_scoped_with_config<R>(&self, config: &C, f: impl FnOnce(&[u8]) -> R) -> Result<R, DecryptionError> {
+        let (_, payload) = self.decrypt_bytes(config)?;
+
+        Ok(f(&payload))
+    }
+
+    /// Creates an [`EncryptedMessage`] from the base64-encoded parts of an existing ciphertext,
+    /// e.g. when migrating data encrypted with another library.
+    ///
+    /// The payload must have been encrypted with XChaCha20Poly1305 using one of the keys of the configuration,
+    /// & must contain a JSON-serialized payload to be decrypted.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`ValidationError::Base64Decoding`] error if a part cannot be base64-decoded.
+    /// - Returns a [`ValidationError::InvalidLength`] error if the nonce isn't 24 bytes long, or the tag isn't 16 bytes long.
+    pub fn from_parts(payload: String, nonce: String, tag: String) -> Result<Self, ValidationError> {
+        let message = EncryptedMessage {
+            payload,
+            headers: EncryptedMessageHeaders {
+                version: 0,
+                nonce,
+                tag: Some(tag),
+                serialization: Serialization::Json,
+                strategy: None,
+                timestamp: None,
+                metadata: BTreeMap::new(),
+                padding: None,
+                key_commitment: None,
+                hmac_tag: None,
+                recipient_keys: Vec::new(),
+            },
+            payload_type: PhantomData,
+            config: PhantomData,
+        };
+        message.validate()?;
+
+        Ok(message)
+    }
+
+    /// Parses an [`EncryptedMessage`] from its JSON representation, rejecting unknown fields, & validates it
+    /// with [`EncryptedMessage::validate`].
+    ///
+    /// Parsing ignores unknown fields by default, so messages written by newer versions of the crate, with new headers,
+    /// can still be read. This catches schema drift & tampering at the boundary instead, e.g. blobs mixing schemas.
+    /// The keys of the metadata aren't restricted.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`ValidationError::Json`] error if the JSON doesn't have the expected structure, or has unknown fields.
+    /// - Returns the errors of [`EncryptedMessage::validate`].
+    pub fn parse_strict(json: &str) -> Result<Self, ValidationError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        deny_unknown_fields(&value, &["p", "h"])?;
+        deny_unknown_fields(&value["h"], &EncryptedMessageHeaders::FIELDS)?;
+
+        Self::try_from(value)
+    }
+
+    /// Checks that the parts of the [`EncryptedMessage`] are valid, without decrypting it.
+    ///
+    /// Messages parsed from JSON are only checked when they're decrypted, so this allows rejecting
+    /// malformed messages early, e.g. at the boundary of an API. See also `TryFrom<serde_json::Value>`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a [`ValidationError::Base64Decoding`] error if a part cannot be base64-decoded.
+    /// - Returns a [`ValidationError::InvalidLength`] error if the nonce isn't 24 bytes long, the tag isn't 16 bytes long,
+    ///   the key commitment or the HMAC tag isn't 32 bytes long, a recipient key isn't 72 bytes long, or the payload is too short to contain the tag when it's appended to it.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let payload_len = validate_part("payload", &self.payload, None)?;
+        validate_part("nonce", &self.headers.nonce, Some(NONCE_SIZE))?;
+        if let Some(key_commitment) = &self.headers.key_commitment {
+            validate_part("key commitment", key_commitment, Some(KEY_COMMITMENT_SIZE))?;
+        }
+        if let Some(hmac_tag) = &self.headers.hmac_tag {
+            validate_part("HMAC tag", hmac_tag, Some(HMAC_TAG_SIZE))?;
+        }
+        for recipient_key in &self.headers.recipient_keys {
+            validate_part("recipient key", recipient_key, Some(WRAPPED_KEY_SIZE))?;
+        }
+
+        match &self.headers.tag {
+            Some(tag) => validate_part("tag", tag, Some(TAG_SIZE)).map(|_| ()),
+            None if payload_len < TAG_SIZE => Err(ValidationError::InvalidLength { field: "payload", expected: TAG_SIZE, actual: payload_len }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the length of the JSON representation of a message, see [`Display`], encrypting a serialized payload
+    /// of the provided length with the provided configuration.
+    ///
+    /// This is useful to check column length limits, e.g. of a MySQL `VARCHAR`, before inserting messages.
+    /// The length of a serialized payload depends on the [`Serialization`], e.g. a JSON string includes its quotes.
+    /// The metadata added by the configuration, i.e. the epoch & the expiration time, is accounted for, but metadata provided
+    /// for a single encryption, e.g. with [`EncryptedMessage::encrypt_with_metadata_and_config`]
+    /// or [`EncryptedMessage::encrypt_with_discriminant_and_config`], isn't.
+    /// Timestamps are assumed to have as many digits as the current time.
+    ///
+    /// The whole configuration is taken, instead of only a [`StrategyKind`], since the length also depends on the format version,
+    /// the serialization format, the tag placement, the HMAC tag, the padding & the metadata it configures.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`EncryptionError::Config`] error if the configuration is invalid, as encryption would.
+    pub fn size_hint_with_config(payload_len: usize, config: &C) -> Result<usize, EncryptionError> {
+        let settings = EncryptionSettings::from_config(config)?;
+
+        let mut encrypted_len = match settings.padding {
+            Some(block_size) => (payload_len + 1).next_multiple_of(block_size.get()),
+            None => payload_len,
+        };
+        if settings.combined_tag {
+            encrypted_len += TAG_SIZE;
+        }
+
+        // The headers are serialized with placeholder values of the right lengths.
+        let message = EncryptedMessage::<P, C> {
+            payload: String::new(),
+            headers: EncryptedMessageHeaders {
+                version: settings.version,
+                nonce: base64::encode([0; NONCE_SIZE]),
+                tag: (!settings.combined_tag).then(|| base64::encode([0; TAG_SIZE])),
+                serialization: settings.serialization,
+                strategy: Some(C::Strategy::KIND),
+                timestamp: settings.timestamp,
+                metadata: settings.metadata,
+                padding: settings.padding,
+                key_commitment: (settings.version >= KEY_COMMITMENT_VERSION).then(|| base64::encode([0; KEY_COMMITMENT_SIZE])),
+                hmac_tag: settings.hmac_tag.then(|| base64::encode([0; HMAC_TAG_SIZE])),
+                recipient_keys: Vec::new(),
+            },
+            payload_type: PhantomData,
+            config: PhantomData,
+        };
+
+        Ok(message.to_string().len() + encrypted_len.div_ceil(3) * 4)
+    }
+
+    /// Returns the version of the message format, which determines how the payload is decrypted.
+    ///
+    /// Messages are written with the version returned by [`Config::format_version`], up to [`FORMAT_VERSION`].
+    /// See [`FORMAT`] for a description of the format.
+    pub fn format_version(&self) -> u8 {
+        self.headers.version
+    }
+
+    /// Returns the metadata attached to the [`EncryptedMessage`], see [`EncryptedMessage::encrypt_with_metadata_and_config`].
+    ///
+    /// The metadata is readable without the key, but it's only guaranteed to be authentic once the message is decrypted or verified.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.headers.metadata
+    }
+
+    /// Returns the discriminant of the payload, if the [`EncryptedMessage`] was encrypted with
+    /// [`EncryptedMessage::encrypt_with_discriminant_and_config`].
+    ///
+    /// Like the rest of the metadata, it's only guaranteed to be authentic once the message is decrypted or verified.
+    pub fn discriminant(&self) -> Option<&str> {
+        self.headers.metadata.get(DISCRIMINANT_METADATA_KEY).map(String::as_str)
+    }
+
+    /// Returns the epoch the [`EncryptedMessage`] was encrypted in, see [`Config::epoch`].
+    ///
+    /// Like the rest of the metadata, it's only guaranteed to be authentic once the message is decrypted or verified.
+    pub fn epoch(&self) -> Option<&str> {
+        self.headers.metadata.get(EPOCH_METADATA_KEY).map(String::as_str)
+    }
+
+    /// Returns the time the [`EncryptedMessage`] expires at, in seconds since the Unix epoch, see [`Config::validity`].
+    ///
+    /// Like the rest of the metadata, it's only guaranteed to be authentic once the message is decrypted or verified.
+    pub fn expires_at(&self) -> Option<i64> {
+        self.headers.metadata.get(EXPIRES_AT_METADATA_KEY)?.parse().ok()
+    }
+
+    /// Returns the strategy the [`EncryptedMessage`] was encrypted with, as stored in its headers.
+    ///
+    /// Unlike the [`Config::Strategy`] type, this reflects how the message was actually encrypted,
+    /// which is useful to audit stored data. Returns `None` for messages encrypted before the strategy was stored.
+    pub fn encoded_strategy(&self) -> Option<StrategyKind> {
+        self.headers.strategy
+    }
+
+    /// Returns the time the [`EncryptedMessage`] was encrypted at, in seconds since the Unix epoch.
+    ///
+    /// Only available for messages encrypted with a configuration where [`Config::stamp_time`] returns `true`.
+    /// **The timestamp isn't authenticated**, so it could have been modified without affecting decryption.
+    /// Don't rely on it for security decisions.
+    pub fn created_at(&self) -> Option<i64> {
+        self.headers.timestamp
+    }
+
+    /// Returns the JSON representation of the headers of the [`EncryptedMessage`], the `h` object described by [`FORMAT`].
+    ///
+    /// This allows tools to inspect all headers, like the nonce, tag or key commitment, without the payload.
+    /// Prefer the typed accessors when they exist, like [`EncryptedMessage::metadata`] or [`EncryptedMessage::encoded_strategy`].
+    pub fn headers_json(&self) -> serde_json::Value {
+        serde_json::to_value(&self.headers).unwrap()
+    }
+
+    /// Changes the configuration type of the [`EncryptedMessage`], without re-encrypting it, e.g. after renaming a configuration type.
+    ///
+    /// **The caller is responsible for the new configuration using compatible keys**, as the message is only decryptable
+    /// with a configuration that has the key it was encrypted with.
+    pub fn remap_config<C2: Config>(self) -> EncryptedMessage<P, C2> {
+        EncryptedMessage {
+            payload: self.payload,
+            headers: self.headers,
+            payload_type: PhantomData,
+            config: PhantomData,
+        }
+    }
+
+    /// Re-encrypts the [`EncryptedMessage`] with the encryption key, if it was encrypted with another key.
+    /// Messages already encrypted with the encryption key are returned as-is.
+    pub(crate) fn rotate_with_config(self, config: &C) -> Result<Self, DecryptionError> {
+        let keys = config.decrypt_keys();
+        let mut payload = Zeroizing::new(Vec::new());
+        let result = self.decrypt_in_place_with_ciphers(&ciphers_for(&keys), DecryptionSettings::from_config(config), &mut payload);
+        config.on_decrypt(result.is_ok());
+        let key_index = result?;
+
+        // Messages encrypted for recipients would lose the other recipients if they were re-encrypted.
+        let key = config.encrypt_key();
+        if !self.headers.recipient_keys.is_empty() || bool::from(keys[key_index].expose_secret().ct_eq(key.expose_secret())) {
+            return Ok(self);
+        }
+
+        // The message keeps its format, timestamp, metadata & padding, only the key changes.
+        let settings = EncryptionSettings {
+            version: self.headers.version,
+            serialization: self.headers.serialization,
+            combined_tag: self.headers.tag.is_none(),
+            hmac_tag: self.headers.hmac_tag.is_some(),
+            timestamp: self.headers.timestamp,
+            metadata: self.headers.metadata.clone(),
+            padding: self.headers.padding,
+            context: config.context(),
+            ..EncryptionSettings::defaults()
+        };
+
+        Ok(Self::encrypt_bytes(payload, &settings, &key))
+    }
+
+    /// Encrypts the serialized payload with the provided key & settings.
+    fn encrypt_bytes(payload: Zeroizing<Vec<u8>>, settings: &EncryptionSettings<'_>, key: &Secret<[u8; 32]>) -> Self {
+        let cipher = XChaCha20Poly1305::new(key.expose_secret().into());
+
+        Self::encrypt_bytes_with_cipher(payload, settings, key, &cipher)
+    }
+
+    /// Encrypts the serialized payload with the provided cipher, which must be built from the provided key.
+    fn encrypt_bytes_with_cipher(payload: Zeroizing<Vec<u8>>, settings: &EncryptionSettings<'_>, key: &Secret<[u8; 32]>, cipher: &XChaCha20Poly1305) -> Self {
+        Self::encrypt_bytes_with_strategy(payload, settings, key, cipher, C::Strategy::KIND, |buffer| {
+            C::Strategy::generate_nonce_for_version(settings.version, buffer, key.expose_secret(), &settings.nonce_context())
+        })
+    }
+
+    /// Encrypts the serialized payload with the provided cipher, using a nonce generated from the padded payload
+    /// by the strategy of the provided kind.
+    fn encrypt_bytes_with_strategy(
+        payload: Zeroizing<Vec<u8>>,
+        settings: &EncryptionSettings<'_>,
+        key: &Secret<[u8; 32]>,
+        cipher: &XChaCha20Poly1305,
+        strategy: StrategyKind,
+        generate_nonce: impl FnOnce(&[u8]) -> [u8; 24],
+    ) -> Self {
+        let mut buffer = payload;
+        if let Some(block_size) = settings.padding {
+            pad(&mut buffer, block_size);
+        }
+
+        let nonce = generate_nonce(&buffer);
+        let key_commitment = (settings.version >= KEY_COMMITMENT_VERSION).then(|| key_commitment(key.expose_secret()));
+        let associated_data = message_associated_data(settings.version, key_commitment.as_ref().map(<[u8; KEY_COMMITMENT_SIZE]>::as_slice), settings.serialization, settings.padding, &settings.metadata);
+        let tag = cipher.encrypt_in_place_detached(&nonce.into(), &associated_data, &mut buffer).unwrap();
+        let key_commitment = key_commitment.map(base64::encode);
+        let hmac_tag = settings.hmac_tag.then(|| base64::encode(hmac_tag(&hmac_key(key.expose_secret()), &nonce, &associated_data, &buffer, &tag)));
+
+        // In the combined form, the tag is appended to the encrypted payload instead of stored in the headers.
+        let tag = if settings.combined_tag {
+            buffer.extend_from_slice(&tag);
+            None
+        } else {
+            Some(base64::encode(tag))
+        };
+
+        EncryptedMessage {
+            payload: base64::encode(&buffer),
+            headers: EncryptedMessageHeaders {
+                version: settings.version,
+                nonce: base64::encode(nonce),
+                tag,
+                serialization: settings.serialization,
+                strategy: Some(strategy),
+                timestamp: settings.timestamp,
+                metadata: settings.metadata.clone(),
+                padding: settings.padding,
+                key_commitment,
+                hmac_tag,
+                recipient_keys: Vec::new(),
+            },
+            payload_type: PhantomData,
+            config: PhantomData,
+        }
+    }
+
+    /// Decrypts the serialized payload, trying all available keys in order until it finds one that works.
+    /// Returns the index of the key that decrypted the payload, along with the serialized payload.
+    fn decrypt_bytes(&self, config: &C) -> Result<(usize, Zeroizing<Vec<u8>>), DecryptionError> {
+        let mut buffer = Zeroizing::new(Vec::new());
+        let key_index = self.decrypt_in_place(config, &mut buffer)?;
+
+        Ok((key_index, buffer))
+    }
+
+    /// Decrypts the serialized payload into the provided buffer, trying all available keys in order until it finds one that works.
+    /// Returns the index of the key that decrypted the payload, & calls [`Config::on_decrypt`] with the outcome.
+    fn decrypt_in_place(&self, config: &C, buffer: &mut Vec<u8>) -> Result<usize, DecryptionError> {
+        let result = self.decrypt_in_place_with_ciphers(&ciphers_for(&config.decrypt_keys()), DecryptionSettings::from_config(config), buffer);
+        config.on_decrypt(result.is_ok());
+
+        result
+    }
+
+    /// Decrypts the serialized payload into the provided buffer, trying all provided ciphers in order until it finds one that works.
+    /// Returns the index of the cipher that decrypted the payload.
+    ///
+    /// This allows operations on many messages to build the ciphers once, see [`ciphers_for`].
+    pub(crate) fn decrypt_in_place_with_ciphers(&self, ciphers: &[KeyCipher], settings: DecryptionSettings, buffer: &mut Vec<u8>) -> Result<usize, DecryptionError> {
+        match self.headers.version {
+            version if version < settings.min_version => return Err(DecryptionError::UnsupportedVersion(version)),
+            0..=FORMAT_VERSION => {},
+            version => return Err(DecryptionError::UnsupportedVersion(version)),
+        }
+
+        if self.headers.recipient_keys.is_empty() {
+            return self.decrypt_payload_in_place(ciphers, settings, buffer);
+        }
+
+        // Messages encrypted for recipients are decrypted with their data key, unwrapped with the first key of a recipient.
+        let wrapped_keys = self.headers.recipient_keys.iter().map(base64::decode).collect::<Result<Vec<_>, _>>()?;
+        let (key_index, data_key) = ciphers
+            .iter()
+            .enumerate()
+            .find_map(|(key_index, KeyCipher { cipher, .. })| wrapped_keys.iter().find_map(|wrapped_key| unwrap_key(cipher, wrapped_key)).map(|data_key| (key_index, data_key)))
+            .ok_or(DecryptionError::Decryption)?;
+        self.decrypt_payload_in_place(&ciphers_for(core::slice::from_ref(&data_key)), settings, buffer)?;
+
+        Ok(key_index)
+    }
+
+    /// Decrypts the encrypted payload into the provided buffer, trying all provided ciphers in order until it finds one that works.
+    /// Returns the index of the cipher that decrypted the payload.
+    fn decrypt_payload_in_place(&self, ciphers: &[KeyCipher], settings: DecryptionSettings, buffer: &mut Vec<u8>) -> Result<usize, DecryptionError> {
+        buffer.clear();
+        base64::decode_into(&self.payload, buffer)?;
+        let nonce = base64::decode(&self.headers.nonce)?;
+
+        // From version 2, only the key the message commits to is tried, so it can't be crafted to decrypt under several keys.
+        let key_commitment = match &self.headers.key_commitment {
+            Some(key_commitment) => Some(base64::decode(key_commitment)?),
+            None if self.headers.version >= KEY_COMMITMENT_VERSION => return Err(DecryptionError::Decryption),
+            None => None,
+        };
+
+        // The HMAC tag is required when the configuration writes it, so it can't be removed to skip its verification.
+        let expected_hmac_tag = match &self.headers.hmac_tag {
+            Some(hmac_tag) => Some(base64::decode(hmac_tag)?),
+            None if settings.hmac_tag => return Err(DecryptionError::Decryption),
+            None => None,
+        };
+
+        // In the combined form, the tag is stored at the end of the encrypted payload.
+        let tag = match &self.headers.tag {
+            Some(tag) => base64::decode(tag)?,
+            None => {
+                let payload_len = buffer.len().checked_sub(TAG_SIZE).ok_or(DecryptionError::Decryption)?;
+                buffer.split_off(payload_len)
+            },
+        };
+
+        let associated_data = message_associated_data(self.headers.version, key_commitment.as_deref(), self.headers.serialization, self.headers.padding, &self.headers.metadata);
+        for (key_index, KeyCipher { cipher, commitment, hmac_key }) in ciphers.iter().enumerate() {
+            if key_commitment.as_ref().is_some_and(|key_commitment| !bool::from(key_commitment.ct_eq(commitment))) {
+                continue;
+            }
+
+            // The HMAC tag is verified before the payload is decrypted, in addition to the auth tag.
+            if expected_hmac_tag.as_ref().is_some_and(|expected| !bool::from(expected.ct_eq(&hmac_tag(hmac_key, &nonce, &associated_data, buffer, &tag)))) {
+                continue;
+            }
+
+            // The tag is verified before the buffer is decrypted, so the buffer is left untouched
+            // if the key doesn't match, & can be reused for the next key.
+            if cipher.decrypt_in_place_detached(nonce.as_slice().into(), &associated_data, buffer, tag.as_slice().into()).is_ok() {
+                // The expiration time is part of the associated data, so it's only checked once it's authenticated.
+                #[cfg(feature = "std")]
+                if self.is_expired() {
+                    buffer.zeroize();
+                    return Err(DecryptionError::Expired);
+                }
+
+                if self.headers.padding.is_some() {
+                    unpad(buffer)?;
+                }
+
+                return Ok(key_index);
+            };
+        }
+
+        Err(DecryptionError::Decryption)
+    }
+}
+
+impl<P, C: Config> EncryptedMessage<P, C> {
+    /// Returns `true` if the message has an expiration time that has passed, or that isn't a valid time.
+    #[cfg(feature = "std")]
+    fn is_expired(&self) -> bool {
+        self.headers.metadata.get(EXPIRES_AT_METADATA_KEY).is_some_and(|expires_at| expires_at.parse().map_or(true, |expires_at: i64| now() > expires_at))
+    }
+}
+
+/// The settings used to encrypt a serialized payload.
+struct EncryptionSettings<'a> {
+    version: u8,
+    serialization: Serialization,
+    combined_tag: bool,
+    hmac_tag: bool,
+    context: &'a [u8],
+    /// The context provided for a single encryption, see [`EncryptedMessage::encrypt_with_context_and_config`].
+    scope: &'a [u8],
+    timestamp: Option<i64>,
+    metadata: BTreeMap<String, String>,
+    padding: Option<NonZeroUsize>,
+    max_len: Option<usize>,
+    reject_empty: bool,
+}
+
+impl<'a> EncryptionSettings<'a> {
+    /// Returns the settings of a configuration that doesn't override any of the [`Config`] methods.
+    fn defaults() -> Self {
+        Self {
+            version: 0,
+            serialization: Serialization::Json,
+            combined_tag: false,
+            hmac_tag: false,
+            context: &[],
+            scope: &[],
+            timestamp: None,
+            metadata: BTreeMap::new(),
+            padding: None,
+            max_len: None,
+            reject_empty: false,
+        }
+    }
+
+    /// Returns the settings of the provided configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConfigError`] error if the version or the block size of the configuration isn't supported, see [`check_config`].
+    fn from_config<C: Config>(config: &'a C) -> Result<Self, ConfigError> {
+        let version = config.format_version();
+        let padding = config.pad_to();
+        check_config(version, padding)?;
+
+        Ok(Self {
+            version,
+            serialization: config.serialization(),
+            combined_tag: config.combined_tag(),
+            hmac_tag: config.hmac_tag(),
+            context: config.context(),
+            scope: &[],
+            #[cfg(feature = "std")]
+            timestamp: config.stamp_time().then(now),
+            #[cfg(not(feature = "std"))]
+            timestamp: None,
+            #[cfg(feature = "std")]
+            metadata: config_metadata(config.epoch(), config.validity()),
+            #[cfg(not(feature = "std"))]
+            metadata: config_metadata(config.epoch()),
+            padding,
+            max_len: config.max_plaintext_len(),
+            reject_empty: config.reject_empty(),
+        })
+    }
+
+    /// Serializes the payload, ensuring it's accepted by the settings, see [`EncryptionSettings::check`].
+    fn serialize<T: Serialize + ?Sized>(&self, payload: &T) -> Result<Zeroizing<Vec<u8>>, EncryptionError> {
+        let payload = Zeroizing::new(self.serialization.serialize(payload)?);
+        self.check(&payload)?;
+
+        Ok(payload)
+    }
+
+    /// Returns an error if the serialized payload exceeds the maximum length, or is empty while empty payloads are rejected.
+    fn check(&self, payload: &[u8]) -> Result<(), EncryptionError> {
+        if let Some(max) = self.max_len.filter(|max| payload.len() > *max) {
+            return Err(EncryptionError::PayloadTooLarge { len: payload.len(), max });
+        }
+
+        if self.reject_empty && self.serialization.is_empty_value(payload) {
+            return Err(EncryptionError::EmptyPayload);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the context nonces are generated with, scoped to the context provided for a single encryption
+    /// & to the metadata, like the epoch, if any.
+    ///
+    /// The metadata is authenticated as associated data, so a payload encrypted with different metadata must never reuse
+    /// a [`Deterministic`] nonce, which would reuse the Poly1305 key & allow forging tags. The configuration context
+    /// is length-prefixed, & each scope is length-prefixed after a byte identifying its kind, so a context provided
+    /// for a single encryption never produces the same nonce as metadata with the same representation.
+    fn nonce_context(&self) -> Cow<'_, [u8]> {
+        if self.scope.is_empty() && self.metadata.is_empty() {
+            return Cow::Borrowed(self.context);
+        }
+
+        let metadata = associated_data(&self.metadata);
+        let mut nonce_context = Vec::with_capacity(3 * 8 + 2 + self.context.len() + self.scope.len() + metadata.len());
+        extend_length_prefixed(&mut nonce_context, self.context);
+        if !self.scope.is_empty() {
+            nonce_context.push(CONTEXT_SCOPE);
+            extend_length_prefixed(&mut nonce_context, self.scope);
+        }
+        if !metadata.is_empty() {
+            nonce_context.push(METADATA_SCOPE);
+            extend_length_prefixed(&mut nonce_context, &metadata);
+        }
+
+        Cow::Owned(nonce_context)
+    }
+}
+
+/// The settings used to decrypt a message. The default settings accept all messages the crate can decrypt.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct DecryptionSettings {
+    min_version: u8,
+    hmac_tag: bool,
+}
+
+impl DecryptionSettings {
+    /// Returns the settings of the provided configuration.
+    pub(crate) fn from_config<C: Config>(config: &C) -> Self {
+        Self { min_version: config.min_format_version(), hmac_tag: config.hmac_tag() }
+    }
+}
+
+/// Returns the metadata storing the epoch & the expiration time of a new message, if any,
+/// see [`Config::epoch`] & [`Config::validity`].
+fn config_metadata(epoch: Option<String>, #[cfg(feature = "std")] validity: Option<core::time::Duration>) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+    if let Some(epoch) = epoch {
+        metadata.insert(String::from(EPOCH_METADATA_KEY), epoch);
+    }
+
+    #[cfg(feature = "std")]
+    if let Some(validity) = validity {
+        let expires_at = now().saturating_add(i64::try_from(validity.as_secs()).unwrap_or(i64::MAX));
+        metadata.insert(String::from(EXPIRES_AT_METADATA_KEY), expires_at.to_string());
+    }
+
+    metadata
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+#[cfg(feature = "std")]
+fn now() -> i64 {
+    let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX)
+}
+
+/// Returns an error if the version of the message format or the block size of the padding of a configuration isn't supported.
+fn check_config(version: u8, padding: Option<NonZeroUsize>) -> Result<(), ConfigError> {
+    if version > FORMAT_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    match padding {
+        Some(block_size) if block_size.get() > MAX_BLOCK_SIZE => Err(ConfigError::UnsupportedBlockSize(block_size.get())),
+        _ => Ok(()),
+    }
+}
+
+/// The byte identifying a context provided for a single encryption in the context nonces are generated with,
+/// see [`EncryptionSettings::nonce_context`].
+const CONTEXT_SCOPE: u8 = 1;
+
+/// The byte identifying the metadata in the context nonces are generated with, see [`EncryptionSettings::nonce_context`].
+const METADATA_SCOPE: u8 = 2;
+
+/// Appends the bytes to the buffer, prefixed with their length.
+fn extend_length_prefixed(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+/// Pads the serialized payload to a multiple of the block size, with a `0x80` byte followed by zeros.
+/// At least one byte is always added, so the padding can be removed unambiguously.
+fn pad(buffer: &mut Vec<u8>, block_size: NonZeroUsize) {
+    buffer.push(0x80);
+    let padded_len = buffer.len().next_multiple_of(block_size.get());
+    buffer.resize(padded_len, 0);
+}
+
+/// Removes the padding added by [`pad`] from the decrypted payload.
+fn unpad(buffer: &mut Vec<u8>) -> Result<(), DecryptionError> {
+    let marker = buffer.iter().rposition(|byte| *byte != 0).ok_or(DecryptionError::Decryption)?;
+    if buffer[marker] != 0x80 {
+        return Err(DecryptionError::Decryption);
+    }
+
+    buffer.truncate(marker);
+    Ok(())
+}
+
+/// Checks that a part of a message is base64-encoded, & has the expected length once decoded if provided.
+/// Returns the decoded length.
+fn validate_part(field: &'static str, value: &str, expected_len: Option<usize>) -> Result<usize, ValidationError> {
+    let decoded = base64::decode(value).map_err(|error| ValidationError::Base64Decoding { field, error })?;
+
+    match expected_len {
+        Some(expected) if decoded.len() != expected => Err(ValidationError::InvalidLength { field, expected, actual: decoded.len() }),
+        _ => Ok(decoded.len()),
+    }
+}
+
+/// A cipher built from a key, along with the commitment to the key & the key used to compute HMAC tags.
+pub(crate) struct KeyCipher {
+    cipher: XChaCha20Poly1305,
+    commitment: [u8; KEY_COMMITMENT_SIZE],
+    hmac_key: Zeroizing<[u8; 32]>,
+}
+
+/// Builds a cipher for each of the provided keys, in order.
+pub(crate) fn ciphers_for(keys: &[Secret<[u8; 32]>]) -> Vec<KeyCipher> {
+    keys
+        .iter()
+        .map(|key| KeyCipher {
+            cipher: XChaCha20Poly1305::new(key.expose_secret().into()),
+            commitment: key_commitment(key.expose_secret()),
+            hmac_key: hmac_key(key.expose_secret()),
+        })
+        .collect()
+}
+
+/// Returns the commitment to a key, an HMAC-SHA256 of a fixed label under the key.
+///
+/// XChaCha20Poly1305 isn't key-committing, so a ciphertext can be crafted to decrypt under two different keys.
+/// Storing the commitment in the headers, & only decrypting with the committed key, prevents this.
+fn key_commitment(key: &[u8; 32]) -> [u8; KEY_COMMITMENT_SIZE] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(b"encrypted-message key commitment");
+
+    mac.finalize().into_bytes().into()
+}
+
+/// Derives the key HMAC tags are computed with from the encryption key, using HKDF-SHA256,
+/// so the same key is never used by both XChaCha20Poly1305 & HMAC-SHA256.
+fn hmac_key(key: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+    let mut hmac_key = Zeroizing::new([0; 32]);
+    Hkdf::<Sha256>::new(None, key).expand(b"encrypted-message hmac tag", hmac_key.as_mut()).unwrap();
+
+    hmac_key
+}
+
+/// Wraps a data key under the key of a recipient, returning the base64-encoded random nonce,
+/// followed by the encrypted data key & its auth tag.
+fn wrap_key(key: &Secret<[u8; 32]>, data_key: &Secret<[u8; 32]>) -> String {
+    let nonce: [u8; NONCE_SIZE] = utilities::random::random();
+    let mut encrypted_key = Zeroizing::new(*data_key.expose_secret());
+    let tag = XChaCha20Poly1305::new(key.expose_secret().into())
+        .encrypt_in_place_detached(&nonce.into(), WRAPPED_KEY_CONTEXT, encrypted_key.as_mut())
+        .unwrap();
+
+    base64::encode([nonce.as_slice(), encrypted_key.as_slice(), tag.as_slice()].concat())
+}
+
+/// Unwraps a data key wrapped by [`wrap_key`], returning `None` if it wasn't wrapped under the key of the cipher.
+fn unwrap_key(cipher: &XChaCha20Poly1305, wrapped_key: &[u8]) -> Option<Secret<[u8; 32]>> {
+    if wrapped_key.len() != WRAPPED_KEY_SIZE {
+        return None;
+    }
+
+    let (nonce, rest) = wrapped_key.split_at(NONCE_SIZE);
+    let (encrypted_key, tag) = rest.split_at(32);
+    let mut data_key: [u8; 32] = encrypted_key.try_into().unwrap();
+    cipher.decrypt_in_place_detached(nonce.into(), WRAPPED_KEY_CONTEXT, &mut data_key, tag.into()).ok()?;
+
+    Some(key_generation::into_secret(&mut data_key))
+}
+
+/// Returns the HMAC tag of an encrypted payload, an HMAC-SHA256 of the nonce, the length-prefixed associated data,
+/// the encrypted payload & its auth tag, see [`Config::hmac_tag`].
+fn hmac_tag(hmac_key: &[u8; 32], nonce: &[u8], associated_data: &[u8], payload: &[u8], tag: &[u8]) -> [u8; HMAC_TAG_SIZE] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key).unwrap();
+    mac.update(nonce);
+    mac.update(&(associated_data.len() as u64).to_be_bytes());
+    mac.update(associated_data);
+    mac.update(payload);
+    mac.update(tag);
+
+    mac.finalize().into_bytes().into()
+}
+
+impl<P: Debug + DeserializeOwned + Serialize, C: Config + Default> EncryptedMessage<P, C> {
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt(payload: P) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_config(payload, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_config_using_key`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_using_key(payload: P, key_index: usize) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_config_using_key(payload, &C::default(), key_index)
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_for_recipients_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_for_recipients(payload: P, recipient_keys: &[Secret<[u8; 32]>]) -> Result<Self, EncryptionError> {
+        Self::encrypt_for_recipients_with_config(payload, recipient_keys, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_ref_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_ref<Q: Serialize + ?Sized>(payload: &Q) -> Result<Self, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        Self::encrypt_ref_with_config(payload, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_metadata_and_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_with_metadata(payload: P, metadata: BTreeMap<String, String>) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_metadata_and_config(payload, metadata, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_discriminant_and_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_with_discriminant(payload: P) -> Result<Self, EncryptionError>
+    where
+        P: Discriminant,
+    {
+        Self::encrypt_with_discriminant_and_config(payload, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_in_epoch_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_in_epoch(payload: P, epoch: &str) -> Result<Self, EncryptionError> {
+        Self::encrypt_in_epoch_with_config(payload, epoch, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_context_and_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_with_context(payload: P, context: &[u8]) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_context_and_config(payload, context, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_with_config_as`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_as<S: Strategy>(payload: P) -> Result<Self, EncryptionError> {
+        Self::encrypt_with_config_as::<S>(payload, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt(&self) -> Result<P, DecryptionError> {
+        self.decrypt_with_config(&C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_with_fallback_config`],
+    /// passing `&C::default()` & `&F::default()` as the configurations.
+    pub fn decrypt_with_fallback<F: Config + Default>(&self) -> Result<P, DecryptionError> {
+        self.decrypt_with_fallback_config(&C::default(), &F::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_many_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_many(payloads: Vec<P>) -> Vec<Result<Self, EncryptionError>> {
+        Self::encrypt_many_with_config(payloads, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_many_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_many(messages: &[Self]) -> Vec<Result<P, DecryptionError>> {
+        Self::decrypt_many_with_config(messages, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::blind_index_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn blind_index<Q: Serialize + ?Sized>(payload: &Q) -> Result<String, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        Self::blind_index_with_config(payload, &C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_into_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_into(&self, scratch: &mut Vec<u8>) -> Result<P, DecryptionError> {
+        self.decrypt_into_with_config(&C::default(), scratch)
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_zeroizing_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_zeroizing(&self) -> Result<Zeroizing<P>, DecryptionError>
+    where
+        P: Zeroize,
+    {
+        self.decrypt_zeroizing_with_config(&C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_either_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_either<Q: DeserializeOwned>(&self) -> Result<Either<P, Q>, DecryptionError> {
+        self.decrypt_either_with_config(&C::default())
+    }
+
+    /// Decrypts the payload like [`EncryptedMessage::decrypt`], returning the provided default value if it fails.
+    ///
+    /// **This hides tampered or corrupted messages**, so it should only be used for display purposes,
+    /// e.g. showing a placeholder when a value can't be decrypted.
+    pub fn decrypt_or(&self, default: P) -> P {
+        self.decrypt().unwrap_or(default)
+    }
+
+    /// Decrypts the payload like [`EncryptedMessage::decrypt`], computing a fallback value from the closure if it fails.
+    ///
+    /// **This hides tampered or corrupted messages**, so it should only be used for display purposes.
+    /// See [`EncryptedMessage::decrypt_or`].
+    pub fn decrypt_or_else<F: FnOnce() -> P>(&self, f: F) -> P {
+        self.decrypt().unwrap_or_else(|_| f())
+    }
+}
+
+impl<P, C: Config + Default> EncryptedMessage<P, C> {
+    /// This method is a shorthand for [`EncryptedMessage::verify_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn verify(&self) -> Result<(), DecryptionError> {
+        self.verify_with_config(&C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_scoped_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_scoped<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R, DecryptionError> {
+        self.decrypt_scoped_with_config(&C::default(), f)
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::size_hint_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn size_hint(payload_len: usize) -> Result<usize, EncryptionError> {
+        Self::size_hint_with_config(payload_len, &C::default())
+    }
+}
+
+impl<P: Debug + DeserializeOwned + Serialize, C: Config<Strategy = Deterministic>> EncryptedMessage<P, C> {
+    /// Returns whether the [`EncryptedMessage`] contains the provided payload, without decrypting it.
+    ///
+    /// The payload is encrypted with the encryption key & the format of the message, & the result is compared
+    /// to the message in constant time. Messages encrypted with an older key never match, see [`rotate`] to re-encrypt them.
+    ///
+    /// Only available for the [`Deterministic`] strategy, as other strategies never produce the same message twice.
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::Serialization`] error if the payload cannot be serialized
+    ///   with the [`Serialization`] of the message.
+    pub fn matches_with_config<Q: Serialize + ?Sized>(&self, payload: &Q, config: &C) -> Result<bool, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        let settings = EncryptionSettings {
+            version: self.headers.version,
+            serialization: self.headers.serialization,
+            combined_tag: self.headers.tag.is_none(),
+            hmac_tag: self.headers.hmac_tag.is_some(),
+            timestamp: None,
+            metadata: self.headers.metadata.clone(),
+            padding: self.headers.padding,
+            context: config.context(),
+            ..EncryptionSettings::defaults()
+        };
+        let payload = Zeroizing::new(settings.serialization.serialize(payload)?);
+        let other = Self::encrypt_bytes(payload, &settings, &config.encrypt_key());
+
+        let matches = self.payload.as_bytes().ct_eq(other.payload.as_bytes())
+            & self.headers.nonce.as_bytes().ct_eq(other.headers.nonce.as_bytes())
+            & self.headers.tag.as_deref().unwrap_or_default().as_bytes().ct_eq(other.headers.tag.as_deref().unwrap_or_default().as_bytes());
+
+        Ok(matches.into())
+    }
+}
+
+impl<P: Debug + DeserializeOwned + Serialize, C: Config<Strategy = Deterministic> + Default> EncryptedMessage<P, C> {
+    /// This method is a shorthand for [`EncryptedMessage::matches_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn matches<Q: Serialize + ?Sized>(&self, payload: &Q) -> Result<bool, EncryptionError>
+    where
+        P: Borrow<Q>,
+    {
+        self.matches_with_config(payload, &C::default())
+    }
+}
+
+impl<C: Config> EncryptedMessage<String, C> {
+    /// Decrypts the payload like [`EncryptedMessage::decrypt_with_config`], returning it in a [`Zeroizing`] string,
+    /// so the plaintext is wiped from memory when it's dropped.
+    ///
+    /// Only the returned string is zeroized, copies made by the caller aren't.
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_str_with_config(&self, config: &C) -> Result<Zeroizing<String>, DecryptionError> {
+        self.decrypt_zeroizing_with_config(config)
+    }
+
+    /// Decrypts the payload like [`EncryptedMessage::decrypt_with_config`], returning it as a [`SecretString`],
+    /// so the plaintext is redacted from its [`Debug`] representation & zeroized when it's dropped.
+    ///
+    /// This is useful for credentials, which can then be handled like the keys of the [`Config`].
+    ///
+    /// # Errors
+    ///
+    /// See [`EncryptedMessage::decrypt_with_config`].
+    pub fn decrypt_secret_with_config(&self, config: &C) -> Result<SecretString, DecryptionError> {
+        self.decrypt_with_config(config).map(SecretString::new)
+    }
+}
+
+impl<C: Config + Default> EncryptedMessage<String, C> {
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_str_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_str(&self) -> Result<Zeroizing<String>, DecryptionError> {
+        self.decrypt_str_with_config(&C::default())
+    }
+
+    /// This method is a shorthand for [`EncryptedMessage::decrypt_secret_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn decrypt_secret(&self) -> Result<SecretString, DecryptionError> {
+        self.decrypt_secret_with_config(&C::default())
+    }
+}
+
+impl<C: Config> EncryptedMessage<serde_json::Value, C> {
+    /// Creates an [`EncryptedMessage`] from raw JSON, encrypting its text as-is instead of parsing & serializing it again.
+    ///
+    /// This is useful to encrypt pre-serialized JSON, e.g. in a proxy. The payload is always stored as JSON,
+    /// regardless of [`Config::serialization`], & is decrypted into a [`serde_json::Value`], or any other type
+    /// it can be deserialized into, e.g. after [`EncryptedMessage::remap_config`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns an [`EncryptionError::PayloadTooLarge`] error if the raw JSON exceeds [`Config::max_plaintext_len`].
+    /// - Returns an [`EncryptionError::EmptyPayload`] error if the raw JSON is empty & [`Config::reject_empty`] returns `true`.
+    pub fn encrypt_raw_json_with_config(raw: &RawValue, config: &C) -> Result<Self, EncryptionError> {
+        let settings = EncryptionSettings { serialization: Serialization::Json, ..EncryptionSettings::from_config(config)? };
+        settings.check(raw.get().as_bytes())?;
+        let payload = Zeroizing::new(raw.get().as_bytes().to_vec());
+
+        Ok(Self::encrypt_bytes(payload, &settings, &config.encrypt_key()))
+    }
+}
+
+impl<C: Config + Default> EncryptedMessage<serde_json::Value, C> {
+    /// This method is a shorthand for [`EncryptedMessage::encrypt_raw_json_with_config`],
+    /// passing `&C::default()` as the configuration.
+    pub fn encrypt_raw_json(raw: &RawValue) -> Result<Self, EncryptionError> {
+        Self::encrypt_raw_json_with_config(raw, &C::default())
+    }
+}
+
+// Implemented manually, as deriving it would require the payload type to implement `Debug`, which no field needs.
+#[cfg(not(feature = "redact-debug"))]
+impl<P, C: Config> Debug for EncryptedMessage<P, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedMessage")
+            .field("payload", &self.payload)
+            .field("headers", &self.headers)
+            .field("payload_type", &self.payload_type)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+#[cfg(feature = "redact-debug")]
+impl<P, C: Config> Debug for EncryptedMessage<P, C> {
+    /// Writes the length of the encrypted payload, hiding the payload & its headers.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("EncryptedMessage");
+        match base64::decode(&self.payload) {
+            Ok(payload) => debug.field("payload", &format_args!("<redacted {} bytes>", payload.len())),
+            Err(_) => debug.field("payload", &format_args!("<redacted>")),
+        };
+
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl<P: Debug + DeserializeOwned + Serialize + Default, C: Config + Default> Default for EncryptedMessage<P, C> {
+    /// Encrypts the default payload, e.g. an empty string, allowing `..Default::default()` in struct initialization.
+    ///
+    /// `Default` can't return errors, so this panics if the payload can't be serialized, which only happens
+    /// for payload types that can't be represented with the configured [`Serialization`], e.g. maps with non-string keys in JSON.
+    /// Use [`EncryptedMessage::encrypt`] with `P::default()` to handle the error instead.
+    fn default() -> Self {
+        Self::encrypt(P::default()).expect("The default payload could not be encrypted.")
+    }
+}
+
+impl<P, C: Config> Display for EncryptedMessage<P, C> {
+    /// Writes the compact JSON representation of the [`EncryptedMessage`].
+    /// Only the encrypted payload & its headers are written, never the plaintext.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl<P, C: Config> FromStr for EncryptedMessage<P, C> {
+    type Err = serde_json::Error;
+
+    /// Parses an [`EncryptedMessage`] from its JSON representation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+impl<P, C: Config> TryFrom<&str> for EncryptedMessage<P, C> {
+    type Error = serde_json::Error;
+
+    /// Parses an [`EncryptedMessage`] from its JSON representation.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl<P, C: Config> TryFrom<String> for EncryptedMessage<P, C> {
+    type Error = serde_json::Error;
+
+    /// Parses an [`EncryptedMessage`] from its JSON representation, e.g. as stored in a text column.
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl<P, C: Config> TryFrom<EncryptedMessage<P, C>> for String {
+    type Error = serde_json::Error;
+
+    /// Returns the compact JSON representation of the [`EncryptedMessage`], e.g. to store it in a text column.
+    fn try_from(message: EncryptedMessage<P, C>) -> Result<Self, Self::Error> {
+        serde_json::to_string(&message)
+    }
+}
+
+impl<P, C: Config> TryFrom<serde_json::Value> for EncryptedMessage<P, C> {
+    type Error = ValidationError;
+
+    /// Parses an [`EncryptedMessage`] from its JSON representation, & validates it with [`EncryptedMessage::validate`].
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let message: Self = serde_json::from_value(value)?;
+        message.validate()?;
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::testing::{
+        SeededSource,
+        TestConfigCombinedTag,
+        TestConfigDeterministic,
+        TestConfigHmacTag,
+        TestConfigLatestFormat,
+        TestConfigLimited,
+        TestConfigPadded,
+        TestConfigRandomized,
+        TestConfigSeeded,
+        TestConfigTimestamped,
+    };
+    #[cfg(feature = "cbor")]
+    use crate::testing::TestConfigCbor;
+    #[cfg(feature = "postcard")]
+    use crate::testing::TestConfigPostcard;
+
+    mod encrypt {
+        use super::*;
+
+        #[test]
+        fn deterministic() {
+            assert_eq!(
+                EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap(),
+                EncryptedMessage {
+                    payload: "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF".to_string(),
+                    headers: EncryptedMessageHeaders {
+                        version: 0,
+                        nonce: "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0".to_string(),
+                        tag: Some("uXQhmffPV/1D7qG8stw6vA==".to_string()),
+                        serialization: Serialization::Json,
+                        strategy: Some(StrategyKind::Deterministic),
+                        timestamp: None,
+                        metadata: BTreeMap::new(),
+                        padding: None,
+                        key_commitment: None,
+                        hmac_tag: None,
+                        recipient_keys: Vec::new(),
+                    },
+                    payload_type: PhantomData,
+                    config: PhantomData,
+                },
+            );
+        }
+
+        #[test]
+        fn randomized() {
+            let payload = "much secret much secure".to_string();
+
+            // Test that the encrypted messages never match, even when they contain the same payload.
+            assert_ne!(
+                EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload.clone()).unwrap(),
+                EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload).unwrap(),
+            );
+        }
+
+        #[test]
+        fn randomized_with_seeded_source() {
+            SeededSource::seed(0);
+            let message = EncryptedMessage::<String, TestConfigSeeded>::encrypt("much secret much secure".to_string()).unwrap();
+
+            // Test that the nonce comes from the seeded source, making the message reproducible.
+            assert_eq!(
+                message.to_string(),
+                r#"{"p":"7bqmQT1lbTK7CAdK2OTYMbrpx3b9JTnd/A==","h":{"iv":"svf1gdbePAaoIv1ufoJl+8APhAFpalvc","at":"xdFT2hcOp0a9VIjK//Wnmw==","s":"r"}}"#,
+            );
+            assert_eq!(message.decrypt().unwrap(), "much secret much secure");
+        }
+
+        #[test]
+        fn borrowed_payload() {
+            // Test that a borrowed payload produces the same encrypted message as its owned form.
+            assert_eq!(
+                EncryptedMessage::<String, TestConfigDeterministic>::encrypt_ref("rigo does pretty codes").unwrap(),
+                EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap(),
+            );
+        }
+
+        #[test]
+        fn test_serialization_error() {
+            // A map with non-string keys can't be serialized into JSON.
+            let map = std::collections::HashMap::<[u8; 2], String>::from([([1, 2], "Hi".to_string())]);
+            assert!(matches!(EncryptedMessage::<_, TestConfigDeterministic>::encrypt(map).unwrap_err(), EncryptionError::Serialization(_)));
+        }
+    }
+
+    mod decrypt {
+        use super::*;
+
+        #[test]
+        fn decrypts_correctly() {
+            let payload = "hi :D".to_string();
+            let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt(payload.clone()).unwrap();
+            assert_eq!(message.decrypt().unwrap(), payload);
+        }
+
+        #[test]
+        fn decrypts_into_scratch_buffer() {
+            let mut scratch = Vec::with_capacity(64);
+            for payload in ["hi :D", "rigo is cool"] {
+                let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt(payload.to_string()).unwrap();
+                assert_eq!(message.decrypt_into(&mut scratch).unwrap(), payload);
+
+                // Test that the scratch buffer is zeroized, but keeps its capacity.
+                assert!(scratch.is_empty());
+                assert!(scratch.capacity() >= 64);
+            }
+
+            // Test that the scratch buffer is zeroized on errors too.
+            let mut message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :D".to_string()).unwrap();
+            message.headers.tag = Some("6NLYKDiHNRkpwoQusf9BaA==".to_string());
+            assert!(matches!(message.decrypt_into(&mut scratch).unwrap_err(), DecryptionError::Decryption));
+            assert!(scratch.is_empty());
+        }
+
+        #[test]
+        fn test_base64_decoding_error() {
+            fn generate() -> EncryptedMessage<String, TestConfigDeterministic> {
+                EncryptedMessage::encrypt("hi :)".to_string()).unwrap()
+            }
+
+            // Test invalid payload.
+            let mut message = generate();
+            message.payload = "invalid".to_string();
+            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+
+            // Test invalid nonce.
+            let mut message = generate();
+            message.headers.nonce = "invalid".to_string();
+            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+
+            // Test invalid tag.
+            let mut message = generate();
+            message.headers.tag = Some("invalid".to_string());
+            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Base64Decoding(_)));
+        }
+
+        #[test]
+        fn test_decryption_error() {
+            // Created using a random disposed key not used in other tests.
+            let message = EncryptedMessage {
+                payload: "c+cOk5DA9y/4LulYA+WCAxFjI8WGbTVK".to_string(),
+                headers: EncryptedMessageHeaders {
+                    version: 0,
+                    nonce: "dBI9t1Y8mUBea+b0nyWXlTeoCdrNPLkg".to_string(),
+                    tag: Some("6NLYKDiHNRkpwoQusf9BaA==".to_string()),
+                    serialization: Serialization::Json,
+                    strategy: None,
+                    timestamp: None,
+                    metadata: BTreeMap::new(),
+                    padding: None,
+                    key_commitment: None,
+                    hmac_tag: None,
+                    recipient_keys: Vec::new(),
+                },
+                payload_type: PhantomData::<fn() -> String>,
+                config: PhantomData::<fn() -> TestConfigDeterministic>,
+            };
+
+            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
+        }
+
+        #[test]
+        fn test_deserialization_error() {
+            let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+
+            // Change the payload type to an integer, even though the initial payload was serialized as a string.
+            let message = EncryptedMessage {
+                payload: message.payload,
+                headers: message.headers,
+                payload_type: PhantomData::<fn() -> u8>,
+                config: message.config,
+            };
+
+            let error = message.decrypt().unwrap_err();
+            assert!(matches!(error, DecryptionError::Deserialization { type_name: "u8", .. }));
+            assert_eq!(error.to_string(), "The payload could not be deserialized into `u8`.");
+        }
+    }
+
+    #[test]
+    fn encrypt_with_context() {
+        let encrypt = |context: &[u8]| EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_context("rigo".to_string(), context).unwrap();
+
+        // Test that messages are deterministic within a context, but not across contexts.
+        assert_eq!(encrypt(b"2024-06-01"), encrypt(b"2024-06-01"));
+        assert_ne!(encrypt(b"2024-06-01"), encrypt(b"2024-06-02"));
+        assert_ne!(encrypt(b"2024-06-01"), EncryptedMessage::encrypt("rigo".to_string()).unwrap());
+        assert_eq!(encrypt(b"2024-06-01").decrypt().unwrap(), "rigo");
+
+        // Test that an empty context has no effect.
+        assert_eq!(encrypt(b""), EncryptedMessage::encrypt("rigo".to_string()).unwrap());
+
+        // Test that a context never produces the same nonce as metadata with the same representation.
+        let metadata = BTreeMap::from([("k".to_string(), "v".to_string())]);
+        let with_metadata = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("rigo".to_string(), metadata).unwrap();
+        assert_ne!(encrypt(br#"{"k":"v"}"#).headers.nonce, with_metadata.headers.nonce);
+    }
+
+    #[test]
+    fn format() {
+        let metadata = BTreeMap::from([("tenant".to_string(), "rigo".to_string())]);
+        let message = EncryptedMessage::<String, TestConfigTimestamped>::encrypt_with_metadata("hi :)".to_string(), metadata).unwrap();
+        assert_eq!(message.format_version(), 0);
+
+        // Test that the schema describes all the fields.
+        let schema: serde_json::Value = serde_json::from_str(FORMAT).unwrap();
+        let json = serde_json::to_value(&message).unwrap();
+        for field in json.as_object().unwrap().keys() {
+            assert!(schema["properties"].get(field).is_some(), "`{field}` is missing from the schema.");
+        }
+        for header in json["h"].as_object().unwrap().keys() {
+            assert!(schema["properties"]["h"]["properties"].get(header).is_some(), "`{header}` is missing from the schema.");
+        }
+    }
+
+    #[test]
+    fn default() {
+        #[derive(Default)]
+        struct User {
+            diary: EncryptedMessage<String, TestConfigRandomized>,
+            lucky_numbers: EncryptedMessage<Vec<u32>, TestConfigRandomized>,
+        }
+
+        let user = User { lucky_numbers: EncryptedMessage::encrypt(vec![7]).unwrap(), ..Default::default() };
+        assert_eq!(user.diary.decrypt().unwrap(), "");
+        assert_eq!(user.lucky_numbers.decrypt().unwrap(), [7]);
+    }
+
+    #[test]
+    fn size_hint() {
+        fn assert_size_hint<C: Config + Default>(payload: &str) {
+            let message = EncryptedMessage::<String, C>::encrypt(payload.to_string()).unwrap();
+            let payload_len = serde_json::to_string(payload).unwrap().len();
+            assert_eq!(EncryptedMessage::<String, C>::size_hint(payload_len).unwrap(), message.to_string().len());
+        }
+
+        for payload in ["", "hi :)", "rigo does pretty codes", "rigo does pretty codes, & even prettier docs"] {
+            assert_size_hint::<TestConfigDeterministic>(payload);
+            assert_size_hint::<TestConfigRandomized>(payload);
+            assert_size_hint::<TestConfigCombinedTag>(payload);
+            assert_size_hint::<TestConfigHmacTag>(payload);
+            assert_size_hint::<TestConfigLatestFormat>(payload);
+            assert_size_hint::<TestConfigPadded>(payload);
+            assert_size_hint::<TestConfigTimestamped>(payload);
+        }
+    }
+
+    #[test]
+    fn discriminant() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        enum Event {
+            Signup { email: String },
+            Payment { amount: u32 },
+        }
+
+        impl Discriminant for Event {
+            fn discriminant(&self) -> String {
+                match self {
+                    Event::Signup { .. } => "signup".to_string(),
+                    Event::Payment { .. } => "payment".to_string(),
+                }
+            }
+        }
+
+        let message = EncryptedMessage::<Event, TestConfigRandomized>::encrypt_with_discriminant(Event::Payment { amount: 42 }).unwrap();
+        assert_eq!(message.discriminant(), Some("payment"));
+        assert_eq!(message.decrypt().unwrap(), Event::Payment { amount: 42 });
+
+        // Test that the discriminant is authenticated.
+        let mut tampered = message;
+        tampered.headers.metadata.insert(DISCRIMINANT_METADATA_KEY.to_string(), "signup".to_string());
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        let message = EncryptedMessage::<Event, TestConfigRandomized>::encrypt(Event::Signup { email: "rigo@example.com".to_string() }).unwrap();
+        assert!(message.discriminant().is_none());
+    }
+
+    #[test]
+    fn metadata() {
+        let metadata = BTreeMap::from([("tenant".to_string(), "rigo".to_string()), ("schema".to_string(), "2".to_string())]);
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("hi :)".to_string(), metadata.clone()).unwrap();
+        assert_eq!(message.metadata(), &metadata);
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["m"], json!({ "schema": "2", "tenant": "rigo" }));
+        assert_eq!(message.decrypt().unwrap(), "hi :)");
+
+        // Test that the metadata is authenticated.
+        let mut tampered = message;
+        tampered.headers.metadata.insert("tenant".to_string(), "someone".to_string());
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that deterministic nonces depend on the metadata, so the Poly1305 key is never reused with different associated data.
+        let first = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("hi :)".to_string(), BTreeMap::from([("k".to_string(), "1".to_string())])).unwrap();
+        let second = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("hi :)".to_string(), BTreeMap::from([("k".to_string(), "2".to_string())])).unwrap();
+        assert_ne!(first.headers.nonce, second.headers.nonce);
+        assert_ne!(first.payload, second.payload);
+        assert_eq!(first, EncryptedMessage::encrypt_with_metadata("hi :)".to_string(), BTreeMap::from([("k".to_string(), "1".to_string())])).unwrap());
+        assert!(first.matches("hi :)").unwrap());
+
+        // Test that messages without metadata don't store it.
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert!(message.metadata().is_empty());
+        assert!(serde_json::to_value(&message.headers).unwrap().get("m").is_none());
+    }
+
+    #[test]
+    fn combined_tag() {
+        let message = EncryptedMessage::<String, TestConfigCombinedTag>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.headers.tag, None);
+        assert!(serde_json::to_value(&message.headers).unwrap().get("at").is_none());
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that the payload is in the combined AEAD form, readable by other libraries.
+        let cipher = XChaCha20Poly1305::new_from_slice(b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").unwrap();
+        let nonce = base64::decode(&message.headers.nonce).unwrap();
+        let payload = base64::decode(&message.payload).unwrap();
+        assert_eq!(payload.len(), r#""rigo does pretty codes""#.len() + TAG_SIZE);
+        assert_eq!(
+            chacha20poly1305::aead::Aead::decrypt(&cipher, nonce.as_slice().into(), payload.as_slice()).unwrap(),
+            br#""rigo does pretty codes""#,
+        );
+
+        // Test that payloads too short to contain a tag fail to decrypt.
+        let mut message = message;
+        message.payload = base64::encode(b"short");
+        assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
+    }
+
+    #[test]
+    fn hmac_tag() {
+        let message = EncryptedMessage::<String, TestConfigHmacTag>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+        let hmac_tag = base64::decode(message.headers.hmac_tag.as_ref().unwrap()).unwrap();
+        assert_eq!(hmac_tag.len(), HMAC_TAG_SIZE);
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        message.validate().unwrap();
+
+        // Test that the tag covers the nonce, the encrypted payload & its auth tag.
+        let key = super::hmac_key(b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt");
+        let nonce = base64::decode(&message.headers.nonce).unwrap();
+        let payload = base64::decode(&message.payload).unwrap();
+        let tag = base64::decode(message.headers.tag.as_ref().unwrap()).unwrap();
+        assert_eq!(hmac_tag, super::hmac_tag(&key, &nonce, &[], &payload, &tag));
+
+        // Test that messages without the setting don't store a tag.
+        let message_without_tag = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(message_without_tag.headers.hmac_tag.is_none());
+
+        // Test that the tag is verified, & kept when the message is rotated.
+        let mut tampered: EncryptedMessage<String, TestConfigHmacTag> = message.to_string().parse().unwrap();
+        tampered.headers.hmac_tag = Some(base64::encode([0; HMAC_TAG_SIZE]));
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that the tag can't be stripped to skip its verification.
+        let mut stripped: EncryptedMessage<String, TestConfigHmacTag> = message.to_string().parse().unwrap();
+        stripped.headers.hmac_tag = None;
+        assert!(matches!(stripped.decrypt().unwrap_err(), DecryptionError::Decryption));
+        assert!(matches!(stripped.verify().unwrap_err(), DecryptionError::Decryption));
+
+        let rotated = message.rotate_with_config(&TestConfigHmacTag).unwrap();
+        assert!(rotated.headers.hmac_tag.is_some());
+        assert_eq!(rotated.decrypt().unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn encrypt_using_key() {
+        let config = TestConfigDeterministic;
+        let message = EncryptedMessage::<String, _>::encrypt_with_config_using_key("rigo does pretty codes".to_string(), &config, 1).unwrap();
+        assert_ne!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that the message was encrypted with the second key.
+        let (key_index, _) = message.decrypt_bytes(&config).unwrap();
+        assert_eq!(key_index, 1);
+
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigDeterministic>::encrypt_using_key("rigo does pretty codes".to_string(), 2).unwrap_err(),
+            EncryptionError::KeyIndexOutOfRange { index: 2, len: 2 },
+        ));
+    }
+
+    #[test]
+    fn decrypt_str() {
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        let payload: Zeroizing<String> = message.decrypt_str().unwrap();
+        assert_eq!(*payload, "rigo does pretty codes");
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_with_key() {
+        let key = TestConfigDeterministic.primary_key();
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_key("rigo does pretty codes".to_string(), &key).unwrap();
+        assert_eq!(message.decrypt_with_key(&key).unwrap(), "rigo does pretty codes");
+
+        // Test that the messages match the ones encrypted with a configuration using the same key.
+        assert_eq!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+
+        let other_key = TestConfigDeterministic.keys().remove(1);
+        assert!(matches!(message.decrypt_with_key(&other_key).unwrap_err(), DecryptionError::Decryption));
+    }
+
+    #[test]
+    fn decrypt_either() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct LegacyPayload {
+            name: String,
+        }
+
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.decrypt_either::<LegacyPayload>().unwrap(), Either::Left("rigo does pretty codes".to_string()));
+
+        // Test that payloads of the fallback type are deserialized into it.
+        let legacy = EncryptedMessage::<LegacyPayload, TestConfigRandomized>::encrypt(LegacyPayload { name: "rigo".to_string() }).unwrap();
+        let legacy: EncryptedMessage<String, TestConfigRandomized> = legacy.to_string().parse().unwrap();
+        assert_eq!(legacy.decrypt_either::<LegacyPayload>().unwrap(), Either::Right(LegacyPayload { name: "rigo".to_string() }));
+
+        // Test that the error of the payload type is returned if neither type matches.
+        let error = legacy.decrypt_either::<u32>().unwrap_err();
+        assert!(matches!(error, DecryptionError::Deserialization { type_name, .. } if type_name == core::any::type_name::<String>()));
+    }
+
+    #[test]
+    fn decrypt_secret() {
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        let payload = message.decrypt_secret().unwrap();
+        assert_eq!(payload.expose_secret(), "rigo does pretty codes");
+        assert!(!format!("{payload:?}").contains("rigo does pretty codes"));
+    }
+
+    #[test]
+    fn encrypt_raw_json() {
+        let raw = RawValue::from_string(r#"{"codes":"pretty","name":"rigo"}"#.to_string()).unwrap();
+        let message = EncryptedMessage::<serde_json::Value, TestConfigDeterministic>::encrypt_raw_json(&raw).unwrap();
+        assert_eq!(message, EncryptedMessage::encrypt(json!({ "name": "rigo", "codes": "pretty" })).unwrap());
+        assert_eq!(message.decrypt().unwrap(), json!({ "name": "rigo", "codes": "pretty" }));
+    }
+
+    #[test]
+    fn decrypt_zeroizing() {
+        let message = EncryptedMessage::<Vec<u8>, TestConfigRandomized>::encrypt(vec![1, 2, 3]).unwrap();
+        let payload: Zeroizing<Vec<u8>> = message.decrypt_zeroizing().unwrap();
+        assert_eq!(*payload, [1, 2, 3]);
+    }
+
+    #[test]
+    fn padding() {
+        let short = EncryptedMessage::<String, TestConfigPadded>::encrypt("1".to_string()).unwrap();
+        let long = EncryptedMessage::<String, TestConfigPadded>::encrypt("12".to_string()).unwrap();
+        assert_eq!(short.headers.padding, NonZeroUsize::new(16));
+        assert!(short.to_string().contains(r#""pd":16"#));
+
+        // Test that payloads of different lengths have the same length once padded.
+        assert_eq!(base64::decode(&short.payload).unwrap().len(), 16);
+        assert_eq!(base64::decode(&long.payload).unwrap().len(), 16);
+        assert_eq!(short.decrypt().unwrap(), "1");
+        assert_eq!(long.decrypt().unwrap(), "12");
+        assert!(long.matches("12").unwrap());
+
+        // Test that a full block is added to payloads that are already a multiple of the block size.
+        let full = EncryptedMessage::<String, TestConfigPadded>::encrypt("rigo codes! :)".to_string()).unwrap();
+        assert_eq!(base64::decode(&full.payload).unwrap().len(), 32);
+        assert_eq!(full.decrypt().unwrap(), "rigo codes! :)");
+
+        // Test that the block size is authenticated, so the padding header can't be removed, changed or added.
+        let mut tampered: EncryptedMessage<String, TestConfigPadded> = short.to_string().parse().unwrap();
+        tampered.headers.padding = None;
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+        tampered.headers.padding = NonZeroUsize::new(8);
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        let mut tampered: EncryptedMessage<String, TestConfigPadded> = EncryptedMessage::<String, TestConfigRandomized>::encrypt("12".to_string()).unwrap().to_string().parse().unwrap();
+        tampered.headers.padding = NonZeroUsize::new(16);
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that messages with an oversized padding header are rejected before padding can allocate.
+        let mut json: serde_json::Value = serde_json::to_value(&long).unwrap();
+        json["h"]["pd"] = serde_json::json!(u64::MAX);
+        assert!(serde_json::from_value::<EncryptedMessage<String, TestConfigPadded>>(json.clone()).is_err());
+        json["h"]["pd"] = serde_json::json!(MAX_BLOCK_SIZE + 1);
+        assert!(serde_json::from_value::<EncryptedMessage<String, TestConfigPadded>>(json).is_err());
+    }
+
+    #[test]
+    fn invalid_config() {
+        #[derive(Debug, Default)]
+        struct InvalidConfig {
+            format_version: u8,
+            pad_to: usize,
+        }
+
+        impl Config for InvalidConfig {
+            type Strategy = <TestConfigRandomized as Config>::Strategy;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigRandomized.keys()
+            }
+
+            fn format_version(&self) -> u8 {
+                self.format_version
+            }
+
+            fn pad_to(&self) -> Option<NonZeroUsize> {
+                NonZeroUsize::new(self.pad_to)
+            }
+        }
+
+        // Test that unsupported configurations return an error instead of panicking.
+        let config = InvalidConfig { format_version: FORMAT_VERSION + 1, pad_to: 0 };
+        assert!(matches!(
+            EncryptedMessage::<String, InvalidConfig>::encrypt_with_config("rigo".to_string(), &config).unwrap_err(),
+            EncryptionError::Config(ConfigError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1,
+        ));
+
+        let config = InvalidConfig { format_version: FORMAT_VERSION, pad_to: MAX_BLOCK_SIZE + 1 };
+        assert!(matches!(
+            EncryptedMessage::<String, InvalidConfig>::encrypt_with_config("rigo".to_string(), &config).unwrap_err(),
+            EncryptionError::Config(ConfigError::UnsupportedBlockSize(block_size)) if block_size == MAX_BLOCK_SIZE + 1,
+        ));
+        assert!(EncryptedMessage::<String, InvalidConfig>::encrypt_many_with_config(vec!["rigo".to_string(); 2], &config).iter().all(Result::is_err));
+        assert!(EncryptedMessage::<String, InvalidConfig>::size_hint_with_config(4, &config).is_err());
+    }
+
+    #[test]
+    fn max_plaintext_len() {
+        // Test that payloads are limited by their serialized length, including the quotes of JSON strings.
+        let message = EncryptedMessage::<String, TestConfigLimited>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigLimited>::encrypt("rigo does pretty codes!".to_string()).unwrap_err(),
+            EncryptionError::PayloadTooLarge { len: 25, max: 24 },
+        ));
+
+        let raw = RawValue::from_string(r#""rigo does pretty codes!""#.to_string()).unwrap();
+        assert!(matches!(
+            EncryptedMessage::<serde_json::Value, TestConfigLimited>::encrypt_raw_json(&raw).unwrap_err(),
+            EncryptionError::PayloadTooLarge { len: 25, max: 24 },
+        ));
+    }
+
+    #[test]
+    fn blind_index() {
+        let index = EncryptedMessage::<String, TestConfigRandomized>::blind_index("rigo@example.com").unwrap();
+        assert_eq!(base64::decode(&index).unwrap().len(), 32);
+
+        // Test that the index is deterministic, even with the Randomized strategy.
+        assert_eq!(EncryptedMessage::<String, TestConfigRandomized>::blind_index("rigo@example.com").unwrap(), index);
+        assert_ne!(EncryptedMessage::<String, TestConfigRandomized>::blind_index("someone@example.com").unwrap(), index);
+    }
+
+    #[test]
+    fn matches() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(message.matches("rigo does pretty codes").unwrap());
+        assert!(!message.matches("rigo does ugly codes").unwrap());
+
+        // Test that the format of the message is used.
+        let message = EncryptedMessage::<String, TestConfigCombinedTag>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(message.matches("rigo does pretty codes").unwrap());
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_many() {
+        let messages = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_many(vec!["hi :)".to_string(), "rigo does pretty codes".to_string()])
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // Test that the messages match the ones encrypted individually.
+        assert_eq!(messages[1], EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+
+        let mut tampered = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        tampered.payload = base64::encode(b"tampered");
+
+        let mut messages = messages;
+        messages.push(tampered);
+
+        let payloads = EncryptedMessage::decrypt_many(&messages);
+        assert_eq!(payloads[0].as_ref().unwrap(), "hi :)");
+        assert_eq!(payloads[1].as_ref().unwrap(), "rigo does pretty codes");
+        assert!(matches!(payloads[2], Err(DecryptionError::Decryption)));
+    }
+
+    #[test]
+    fn verify() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert!(message.verify().is_ok());
+
+        // Test that verification doesn't deserialize the payload.
+        let message = EncryptedMessage {
+            payload: message.payload,
+            headers: message.headers,
+            payload_type: PhantomData::<fn() -> u8>,
+            config: message.config,
+        };
+        assert!(message.verify().is_ok());
+
+        // Test that tampered messages fail verification.
+        let mut message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        message.payload = base64::encode(b"tampered");
+        assert!(matches!(message.verify().unwrap_err(), DecryptionError::Decryption));
+    }
+
+    #[test]
+    fn latest_format_version() {
+        let message = EncryptedMessage::<String, TestConfigLatestFormat>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.format_version(), FORMAT_VERSION);
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["v"], FORMAT_VERSION);
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        assert!(message.matches("rigo does pretty codes").unwrap());
+
+        // Test that deterministic nonces are derived differently from the initial version.
+        let initial = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_ne!(message.headers.nonce, initial.headers.nonce);
+
+        // Test that rotated messages keep their version.
+        let message = EncryptedMessage::<String, TestConfigLatestFormat>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+        let rotated = rotate([message], &TestConfigLatestFormat).next().unwrap().unwrap();
+        assert_eq!(rotated.format_version(), FORMAT_VERSION);
+        assert_eq!(rotated, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+    }
+
+    #[test]
+    fn separate_encrypt_and_decrypt_keys() {
+        // Decrypts with the test keys, but encrypts with another key.
+        #[derive(Debug, Default, PartialEq, Eq)]
+        struct SeparateKeysConfig;
+        impl Config for SeparateKeysConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigDeterministic.keys()
+            }
+
+            fn encrypt_key(&self) -> Secret<[u8; 32]> {
+                (*b"0123456789abcdefghijklmnopqrstuv").into()
+            }
+        }
+
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        let message: EncryptedMessage<String, SeparateKeysConfig> = message.to_string().parse().unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that new messages are encrypted with the encryption key, which isn't one of the decryption keys.
+        let new_message = EncryptedMessage::<String, SeparateKeysConfig>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(matches!(new_message.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that messages are rotated to the encryption key.
+        let rotated = rotate([message], &SeparateKeysConfig).next().unwrap().unwrap();
+        assert_eq!(rotated, new_message);
+    }
+
+    #[test]
+    fn decrypt_with_fallback() {
+        #[derive(Debug, Default)]
+        struct OtherConfig;
+        impl Config for OtherConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                vec![(*b"0123456789abcdefghijklmnopqrstuv").into()]
+            }
+        }
+
+        // Test that messages encrypted with either configuration are decrypted.
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.decrypt_with_fallback::<OtherConfig>().unwrap(), "rigo does pretty codes");
+
+        let message = EncryptedMessage::<String, OtherConfig>::encrypt("rigo does pretty codes".to_string()).unwrap().remap_config::<TestConfigDeterministic>();
+        assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
+        assert_eq!(message.decrypt_with_fallback::<OtherConfig>().unwrap(), "rigo does pretty codes");
+
+        // Test that other errors aren't retried with the fallback configuration.
+        let mut message = message;
+        message.headers.version = FORMAT_VERSION + 1;
+        assert!(matches!(message.decrypt_with_fallback::<OtherConfig>().unwrap_err(), DecryptionError::UnsupportedVersion(_)));
+    }
+
+    #[test]
+    fn key_commitment() {
+        let mut message = EncryptedMessage::<String, TestConfigLatestFormat>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+        let keys = TestConfigLatestFormat.keys();
+        assert_eq!(message.headers.key_commitment, Some(base64::encode(super::key_commitment(keys[1].expose_secret()))));
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        message.validate().unwrap();
+
+        // Test that messages of earlier versions don't store a commitment.
+        let initial = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(initial.headers.key_commitment.is_none());
+
+        // Test that the commitment is required from version 2.
+        message.headers.key_commitment = None;
+        assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that only the committed key is tried.
+        message.headers.key_commitment = Some(base64::encode(super::key_commitment(keys[0].expose_secret())));
+        assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        message.headers.key_commitment = Some(base64::encode([0; 16]));
+        assert!(matches!(message.validate().unwrap_err(), ValidationError::InvalidLength { field: "key commitment", expected: 32, actual: 16 }));
+    }
+
+    #[test]
+    fn key_commitment_downgrade() {
+        let message = EncryptedMessage::<String, TestConfigLatestFormat>::encrypt_using_key("rigo does pretty codes".to_string(), 1).unwrap();
+
+        // Test that stripping the version & the commitment is rejected by configurations requiring a commitment.
+        let mut downgraded: EncryptedMessage<String, TestConfigLatestFormat> = message.to_string().parse().unwrap();
+        downgraded.headers.version = 0;
+        downgraded.headers.key_commitment = None;
+        assert!(matches!(downgraded.decrypt().unwrap_err(), DecryptionError::UnsupportedVersion(0)));
+
+        // Test that the version & the commitment are authenticated, so messages can't be downgraded for other configurations either.
+        assert!(matches!(downgraded.decrypt_with_key(&TestConfigLatestFormat.keys()[1]).unwrap_err(), DecryptionError::Decryption));
+        let mut tampered: EncryptedMessage<String, TestConfigLatestFormat> = message.to_string().parse().unwrap();
+        tampered.headers.version = 1;
+        assert!(matches!(tampered.decrypt_with_key(&TestConfigLatestFormat.keys()[1]).unwrap_err(), DecryptionError::Decryption));
+
+        // Test that messages of earlier versions are rejected by configurations requiring a commitment, but not without a configuration.
+        let initial = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert!(matches!(initial.to_string().parse::<EncryptedMessage<String, TestConfigLatestFormat>>().unwrap().decrypt().unwrap_err(), DecryptionError::UnsupportedVersion(0)));
+        assert_eq!(initial.decrypt_with_key(&TestConfigDeterministic.keys()[0]).unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert_eq!(message.headers.version, 0);
+        assert!(serde_json::to_value(&message.headers).unwrap().get("v").is_none());
+
+        // Test that unknown future versions aren't misinterpreted.
+        message.headers.version = 42;
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["v"], 42);
+        assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::UnsupportedVersion(42)));
+        assert!(matches!(message.verify().unwrap_err(), DecryptionError::UnsupportedVersion(42)));
+    }
+
+    #[test]
+    fn decrypt_or() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert_eq!(message.decrypt_or("***".to_string()), "hi :)");
+        assert_eq!(message.decrypt_or_else(|| "***".to_string()), "hi :)");
+
+        // Test that the fallback is returned for tampered messages.
+        let mut message = message;
+        message.payload = base64::encode(b"tampered");
+        assert_eq!(message.decrypt_or("***".to_string()), "***");
+        assert_eq!(message.decrypt_or_else(|| "***".to_string()), "***");
+    }
+
+    #[test]
+    fn created_at() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert_eq!(message.created_at(), None);
+        assert!(serde_json::to_value(&message.headers).unwrap().get("ts").is_none());
+
+        let before = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let message = EncryptedMessage::<String, TestConfigTimestamped>::encrypt("hi :)".to_string()).unwrap();
+        let created_at = message.created_at().unwrap();
+        assert!(created_at >= before && created_at - before < 60);
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["ts"], created_at);
+        assert_eq!(message.decrypt().unwrap(), "hi :)");
+    }
+
+    #[test]
+    fn encoded_strategy() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert_eq!(message.encoded_strategy(), Some(StrategyKind::Deterministic));
+
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt("hi :)".to_string()).unwrap();
+        assert_eq!(message.encoded_strategy(), Some(StrategyKind::Randomized));
+
+        // Test that messages encrypted before the strategy was stored don't have one.
+        let json = r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA=="}}"#;
+        let message = json.parse::<EncryptedMessage<String, TestConfigDeterministic>>().unwrap();
+        assert_eq!(message.encoded_strategy(), None);
+    }
+
+    #[test]
+    fn epochs() {
+        #[derive(Debug, Default, PartialEq, Eq)]
+        struct EpochConfig;
+        impl Config for EpochConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigDeterministic.keys()
+            }
+
+            fn epoch(&self) -> Option<String> {
+                Some("2026-10".to_string())
+            }
+        }
+
+        // Test that the same payload produces the same message within an epoch, but not across epochs.
+        let message = EncryptedMessage::<String, EpochConfig>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.epoch(), Some("2026-10"));
+        assert_eq!(message, EncryptedMessage::encrypt("rigo does pretty codes".to_string()).unwrap());
+        assert_eq!(message, EncryptedMessage::encrypt_in_epoch("rigo does pretty codes".to_string(), "2026-10").unwrap());
+        assert!(message.matches("rigo does pretty codes").unwrap());
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        let past = EncryptedMessage::<String, EpochConfig>::encrypt_in_epoch("rigo does pretty codes".to_string(), "2026-09").unwrap();
+        assert_eq!(past.epoch(), Some("2026-09"));
+        assert_ne!(past.headers.nonce, message.headers.nonce);
+        assert!(past.matches("rigo does pretty codes").unwrap());
+
+        // Test that the nonce isn't only scoped by the metadata, which is authenticated either way.
+        let other = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("rigo does pretty codes".to_string(), BTreeMap::from([("tenant".to_string(), "2026-10".to_string())])).unwrap();
+        assert_ne!(other.headers.nonce, message.headers.nonce);
+    }
+
+    #[test]
+    fn encrypt_as() {
+        // Test that the Deterministic strategy produces the same message as a Deterministic configuration with the same keys.
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt_as::<Deterministic>("rigo does pretty codes".to_string()).unwrap();
+        let deterministic = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.to_string(), deterministic.to_string());
+        assert_eq!(message.encoded_strategy(), Some(StrategyKind::Deterministic));
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_as::<crate::strategy::Randomized>("rigo does pretty codes".to_string()).unwrap();
+        assert_ne!(message, deterministic);
+        assert_eq!(message.encoded_strategy(), Some(StrategyKind::Randomized));
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn headers_json() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(message.headers_json(), json!({ "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0", "at": "uXQhmffPV/1D7qG8stw6vA==", "s": "d" }));
+        assert_eq!(message.headers_json(), serde_json::to_value(&message).unwrap()["h"]);
+    }
+
+    #[test]
+    fn remap_config() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        let json = message.to_string();
+
+        // Test that the message is unchanged, & decryptable with a configuration using the same keys.
+        let message = message.remap_config::<TestConfigRandomized>();
+        assert_eq!(message.to_string(), json);
+        assert_eq!(message.decrypt().unwrap(), "hi :)");
+    }
+
+    #[test]
+    fn eq_compares_strategies() {
+        // Test that messages with the same ciphertext, but different strategy markers, aren't equal.
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        let mut other: EncryptedMessage<String, TestConfigDeterministic> = message.to_string().parse().unwrap();
+        assert_eq!(message, other);
+
+        other.headers.strategy = Some(StrategyKind::Randomized);
+        assert_ne!(message, other);
+
+        other.headers.strategy = None;
+        assert_ne!(message, other);
+    }
+
+    #[test]
+    fn allows_rotating_keys() {
+        // Created using TestConfig's second key.
+        let message = EncryptedMessage {
+            payload: "LC4u257NQw==".to_string(),
+            headers: EncryptedMessageHeaders {
+                version: 0,
+                nonce: "nv6rH50Sn2Po320KT57fg1a3Lyu/IGeG".to_string(),
+                tag: Some("/jK8Y7fOyA+S7/dTxRR3SQ==".to_string()),
+                serialization: Serialization::Json,
+                strategy: None,
+                timestamp: None,
+                metadata: BTreeMap::new(),
+                padding: None,
+                key_commitment: None,
+                hmac_tag: None,
+                recipient_keys: Vec::new(),
+            },
+            payload_type: PhantomData::<fn() -> String>,
+            config: PhantomData::<fn() -> TestConfigDeterministic>,
+        };
+
+        // Ensure that if encrypting the same value, it'll be different since it'll use the new primary key.
+        // Note that we're using the `Deterministic` encryption strategy, so the encrypted message would be the
+        // same if the key was the same.
         let expected_payload = "hi :)".to_string();
         assert_ne!(
             EncryptedMessage::<String, TestConfigDeterministic>::encrypt(expected_payload.clone()).unwrap(),
@@ -422,37 +2974,275 @@ mod tests {
     }
 
     #[test]
-    fn handles_json_types() {
+    fn expiration() {
+        #[derive(Debug, Default)]
+        struct ExpiringConfig;
+        impl Config for ExpiringConfig {
+            type Strategy = crate::strategy::Randomized;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigRandomized.keys()
+            }
+
+            fn validity(&self) -> Option<core::time::Duration> {
+                Some(core::time::Duration::from_secs(3600))
+            }
+        }
+
+        let message = EncryptedMessage::<String, ExpiringConfig>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        let expires_at = message.expires_at().unwrap();
+        assert!((3599..3660).contains(&(expires_at - now())));
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that expired messages, & messages with invalid expiration times, are authenticated but not decrypted.
+        for expires_at in [(now() - 1).to_string(), "soon".to_string()] {
+            let metadata = BTreeMap::from([(EXPIRES_AT_METADATA_KEY.to_string(), expires_at)]);
+            let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt_with_metadata("rigo does pretty codes".to_string(), metadata).unwrap();
+            assert!(matches!(message.decrypt().unwrap_err(), DecryptionError::Expired));
+            assert!(matches!(message.verify().unwrap_err(), DecryptionError::Expired));
+        }
+
+        // Test that messages without an expiration time never expire.
+        assert_eq!(EncryptedMessage::<String, TestConfigRandomized>::encrypt("hi :)".to_string()).unwrap().expires_at(), None);
+    }
+
+    #[test]
+    fn deterministic_expiration() {
+        use core::cell::Cell;
+
+        // The validity is increased between encryptions, like encrypting a second later.
+        #[derive(Debug)]
+        struct ExpiringConfig {
+            validity: Cell<u64>,
+        }
+        impl Config for ExpiringConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigDeterministic.keys()
+            }
+
+            fn validity(&self) -> Option<core::time::Duration> {
+                Some(core::time::Duration::from_secs(self.validity.get()))
+            }
+        }
+
+        let config = ExpiringConfig { validity: Cell::new(3600) };
+        let first = EncryptedMessage::<String, ExpiringConfig>::encrypt_with_config("rigo does pretty codes".to_string(), &config).unwrap();
+        config.validity.set(3601);
+        let second = EncryptedMessage::<String, ExpiringConfig>::encrypt_with_config("rigo does pretty codes".to_string(), &config).unwrap();
+
+        // Test that messages with different expiration times never share a nonce, which would reuse the Poly1305 key.
+        assert_ne!(first.expires_at(), second.expires_at());
+        assert_ne!(first.headers.nonce, second.headers.nonce);
+        assert_ne!(first.payload, second.payload);
+        assert_eq!(first.decrypt_with_config(&config).unwrap(), "rigo does pretty codes");
+        assert_eq!(second.decrypt_with_config(&config).unwrap(), "rigo does pretty codes");
+    }
+
+    #[test]
+    fn rejects_empty_payload() {
+        #[derive(Debug, Default)]
+        struct RejectEmptyConfig;
+        impl Config for RejectEmptyConfig {
+            type Strategy = Deterministic;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigDeterministic.keys()
+            }
+
+            fn reject_empty(&self) -> bool {
+                true
+            }
+        }
+
+        assert!(matches!(EncryptedMessage::<String, RejectEmptyConfig>::encrypt("".to_string()).unwrap_err(), EncryptionError::EmptyPayload));
+        assert!(matches!(EncryptedMessage::<Vec<u8>, RejectEmptyConfig>::encrypt(Vec::new()).unwrap_err(), EncryptionError::EmptyPayload));
+
+        let raw = RawValue::from_string("{}".to_string()).unwrap();
+        assert!(matches!(EncryptedMessage::<serde_json::Value, RejectEmptyConfig>::encrypt_raw_json(&raw).unwrap_err(), EncryptionError::EmptyPayload));
+
+        // Test that `None` & non-empty payloads are still encrypted.
+        assert_eq!(EncryptedMessage::<Option<String>, RejectEmptyConfig>::encrypt(None).unwrap().decrypt().unwrap(), None);
+        assert_eq!(EncryptedMessage::<String, RejectEmptyConfig>::encrypt(" ".to_string()).unwrap().decrypt().unwrap(), " ");
+    }
+
+    /// Tests that all JSON types survive an encryption round-trip with the given configuration.
+    fn assert_handles_json_types<C: Config + Default>() {
+        assert_handles_typed_values::<C>();
+
+        // Object values
+        let encrypted = EncryptedMessage::<serde_json::Value, C>::encrypt(json!({ "a": 1, "b": "hello", "c": false })).unwrap();
+        assert_eq!(encrypted.decrypt().unwrap(), json!({ "a": 1, "b": "hello", "c": false }));
+    }
+
+    fn assert_handles_typed_values<C: Config + Default>() {
         // Nullable values
-        let encrypted = EncryptedMessage::<Option<String>, TestConfigRandomized>::encrypt(None).unwrap();
+        let encrypted = EncryptedMessage::<Option<String>, C>::encrypt(None).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), None);
 
-        let encrypted = EncryptedMessage::<Option<String>, TestConfigRandomized>::encrypt(Some("rigo is cool".to_string())).unwrap();
+        let encrypted = EncryptedMessage::<Option<String>, C>::encrypt(Some("rigo is cool".to_string())).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), Some("rigo is cool".to_string()));
 
         // Boolean values
-        let encrypted = EncryptedMessage::<bool, TestConfigRandomized>::encrypt(true).unwrap();
+        let encrypted = EncryptedMessage::<bool, C>::encrypt(true).unwrap();
         assert_eq!(encrypted.decrypt().unwrap() as u8, 1);
 
         // Integer values
-        let encrypted = EncryptedMessage::<u8, TestConfigRandomized>::encrypt(255).unwrap();
+        let encrypted = EncryptedMessage::<u8, C>::encrypt(255).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), 255);
 
         // Float values
-        let encrypted = EncryptedMessage::<f64, TestConfigRandomized>::encrypt(0.12345).unwrap();
+        let encrypted = EncryptedMessage::<f64, C>::encrypt(0.12345).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), 0.12345);
 
         // String values
-        let encrypted = EncryptedMessage::<String, TestConfigRandomized>::encrypt("rigo is cool".to_string()).unwrap();
+        let encrypted = EncryptedMessage::<String, C>::encrypt("rigo is cool".to_string()).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), "rigo is cool");
 
         // Array values
-        let encrypted = EncryptedMessage::<Vec<u8>, TestConfigRandomized>::encrypt(vec![1, 2, 3]).unwrap();
+        let encrypted = EncryptedMessage::<Vec<u8>, C>::encrypt(vec![1, 2, 3]).unwrap();
         assert_eq!(encrypted.decrypt().unwrap(), vec![1, 2, 3]);
+    }
 
-        // Object values
-        let encrypted = EncryptedMessage::<serde_json::Value, TestConfigRandomized>::encrypt(json!({ "a": 1, "b": "hello", "c": false })).unwrap();
-        assert_eq!(encrypted.decrypt().unwrap(), json!({ "a": 1, "b": "hello", "c": false }));
+    #[test]
+    fn handles_json_types() {
+        assert_handles_json_types::<TestConfigRandomized>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn handles_json_types_with_cbor() {
+        assert_handles_json_types::<TestConfigCbor>();
+
+        // Test that the serialization format is stored in the headers.
+        let message = EncryptedMessage::<String, TestConfigCbor>::encrypt("rigo is cool".to_string()).unwrap();
+        assert_eq!(message.headers.serialization, Serialization::Cbor);
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["e"], "cbor");
+
+        // Test that the serialization format is authenticated, so it can't be changed to deserialize the payload with another format.
+        let mut tampered = EncryptedMessage::<i64, TestConfigRandomized>::encrypt(5).unwrap();
+        tampered.headers.serialization = Serialization::Cbor;
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        let mut tampered = EncryptedMessage::<i64, TestConfigCbor>::encrypt(5).unwrap();
+        tampered.headers.serialization = Serialization::Json;
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn handles_typed_values_with_postcard() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Reading {
+            sensor: String,
+            values: BTreeMap<String, i64>,
+            calibrated: Option<bool>,
+        }
+
+        assert_handles_typed_values::<TestConfigPostcard>();
+
+        let reading = Reading {
+            sensor: "rigo".to_string(),
+            values: BTreeMap::from([("a".to_string(), 1), ("b".to_string(), -1)]),
+            calibrated: Some(false),
+        };
+        let message = EncryptedMessage::<Reading, TestConfigPostcard>::encrypt_ref(&reading).unwrap();
+        assert_eq!(message.decrypt().unwrap(), reading);
+
+        // Test that the serialization format is stored in the headers.
+        assert_eq!(message.headers.serialization, Serialization::Postcard);
+        assert_eq!(serde_json::to_value(&message.headers).unwrap()["e"], "postcard");
+    }
+
+    #[test]
+    fn from_parts() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::from_parts(
+            "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF".to_string(),
+            "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0".to_string(),
+            "uXQhmffPV/1D7qG8stw6vA==".to_string(),
+        ).unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test invalid base64.
+        let error = EncryptedMessage::<String, TestConfigDeterministic>::from_parts(
+            "invalid".to_string(),
+            "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0".to_string(),
+            "uXQhmffPV/1D7qG8stw6vA==".to_string(),
+        ).unwrap_err();
+        assert!(matches!(error, ValidationError::Base64Decoding { field: "payload", .. }));
+
+        // Test invalid lengths.
+        let error = EncryptedMessage::<String, TestConfigDeterministic>::from_parts(
+            "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF".to_string(),
+            base64::encode([0; 12]),
+            "uXQhmffPV/1D7qG8stw6vA==".to_string(),
+        ).unwrap_err();
+        assert!(matches!(error, ValidationError::InvalidLength { field: "nonce", expected: 24, actual: 12 }));
+        assert_eq!(error.to_string(), "The nonce must be 24 bytes long, but is 12 bytes long.");
+    }
+
+    #[test]
+    fn validate() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("hi :)".to_string()).unwrap();
+        assert!(message.validate().is_ok());
+
+        let mut message = message;
+        message.headers.tag = Some(base64::encode([0; 8]));
+        assert!(matches!(message.validate().unwrap_err(), ValidationError::InvalidLength { field: "tag", expected: 16, actual: 8 }));
+
+        // Test payloads too short to contain an appended tag.
+        message.headers.tag = None;
+        message.payload = base64::encode(b"short");
+        assert!(matches!(message.validate().unwrap_err(), ValidationError::InvalidLength { field: "payload", .. }));
+    }
+
+    #[test]
+    fn try_from_json_value() {
+        let value = json!({
+            "p": "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF",
+            "h": {
+                "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0",
+                "at": "uXQhmffPV/1D7qG8stw6vA==",
+            },
+        });
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::try_from(value).unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that malformed messages are rejected eagerly.
+        let value = json!({ "p": "invalid", "h": { "iv": "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0", "at": "uXQhmffPV/1D7qG8stw6vA==" } });
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigDeterministic>::try_from(value).unwrap_err(),
+            ValidationError::Base64Decoding { field: "payload", .. },
+        ));
+
+        let value = json!({ "p": "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF" });
+        assert!(matches!(EncryptedMessage::<String, TestConfigDeterministic>::try_from(value).unwrap_err(), ValidationError::Json(_)));
+    }
+
+    #[test]
+    fn packs_multiple_values() {
+        // Tuples.
+        let payload = ("rigo".to_string(), "very-secret-password".to_string(), 5432_u16);
+        let message = EncryptedMessage::<(String, String, u16), TestConfigRandomized>::encrypt(payload.clone()).unwrap();
+        assert_eq!(message.decrypt().unwrap(), payload);
+
+        // Test that a packed message is smaller than separate messages.
+        let separate_len = EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload.0).unwrap().to_string().len()
+            + EncryptedMessage::<String, TestConfigRandomized>::encrypt(payload.1).unwrap().to_string().len()
+            + EncryptedMessage::<u16, TestConfigRandomized>::encrypt(payload.2).unwrap().to_string().len();
+        assert!(message.to_string().len() + 100 < separate_len);
+
+        // Structs.
+        #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+        struct Credentials {
+            username: String,
+            password: String,
+        }
+
+        let payload = Credentials { username: "rigo".to_string(), password: "very-secret-password".to_string() };
+        let message = EncryptedMessage::<Credentials, TestConfigRandomized>::encrypt_ref(&payload).unwrap();
+        assert_eq!(message.decrypt().unwrap(), payload);
     }
 
     #[test]
@@ -460,11 +3250,20 @@ mod tests {
         let message = EncryptedMessage {
             payload: "48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF".to_string(),
             headers: EncryptedMessageHeaders {
+                version: 0,
                 nonce: "1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0".to_string(),
-                tag: "uXQhmffPV/1D7qG8stw6vA==".to_string(),
+                tag: Some("uXQhmffPV/1D7qG8stw6vA==".to_string()),
+                serialization: Serialization::Json,
+                strategy: None,
+                timestamp: None,
+                metadata: BTreeMap::new(),
+                padding: None,
+                key_commitment: None,
+                hmac_tag: None,
+                recipient_keys: Vec::new(),
             },
-            payload_type: PhantomData::<String>,
-            config: PhantomData::<TestConfigRandomized>,
+            payload_type: PhantomData::<fn() -> String>,
+            config: PhantomData::<fn() -> TestConfigRandomized>,
         };
 
         // To JSON.
@@ -486,4 +3285,177 @@ mod tests {
             message,
         );
     }
+
+    #[test]
+    fn from_str() {
+        let json = r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA=="}}"#;
+        let message = json.parse::<EncryptedMessage<String, TestConfigDeterministic>>().unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        assert_eq!(EncryptedMessage::try_from(json).unwrap(), message);
+
+        // Test invalid JSON.
+        assert!("invalid".parse::<EncryptedMessage<String, TestConfigDeterministic>>().is_err());
+        assert!(EncryptedMessage::<String, TestConfigDeterministic>::try_from(r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_strict() {
+        let json = r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA=="}}"#;
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::parse_strict(json).unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+
+        // Test that unknown fields are rejected, unlike with lenient parsing.
+        for json in [
+            r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA=="},"x":1}"#,
+            r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA==","x":1}}"#,
+        ] {
+            assert!(json.parse::<EncryptedMessage<String, TestConfigDeterministic>>().is_ok());
+            let error = EncryptedMessage::<String, TestConfigDeterministic>::parse_strict(json).unwrap_err();
+            assert!(matches!(&error, ValidationError::Json(error) if error.to_string().starts_with("unknown field `x`")));
+        }
+
+        // Test that metadata keys are free-form, & that the messages are validated.
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt_with_metadata("hi :)".to_string(), BTreeMap::from([("x".to_string(), "1".to_string())])).unwrap();
+        assert!(EncryptedMessage::<String, TestConfigDeterministic>::parse_strict(&message.to_string()).is_ok());
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigDeterministic>::parse_strict(r#"{"p":"","h":{"iv":"c2hvcnQ=","at":"uXQhmffPV/1D7qG8stw6vA=="}}"#).unwrap_err(),
+            ValidationError::InvalidLength { field: "nonce", .. },
+        ));
+
+        // Test that the accepted headers match the ones described by `FORMAT`.
+        let format: serde_json::Value = serde_json::from_str(FORMAT).unwrap();
+        assert!(format["properties"]["h"]["properties"].as_object().unwrap().keys().map(String::as_str).eq(EncryptedMessageHeaders::FIELDS.into_iter().collect::<alloc::collections::BTreeSet<_>>()));
+    }
+
+    #[test]
+    fn decrypt_scoped() {
+        #[derive(Deserialize)]
+        struct Borrowed<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+        }
+
+        let message = EncryptedMessage::<serde_json::Value, TestConfigRandomized>::encrypt(json!({ "name": "rigo" })).unwrap();
+        let borrowed = message.decrypt_scoped(|payload| matches!(serde_json::from_slice::<Borrowed>(payload).unwrap().name, Cow::Borrowed("rigo"))).unwrap();
+        assert!(borrowed);
+
+        // Test that decryption errors are returned without calling the closure.
+        let mut tampered = message;
+        tampered.headers.nonce = base64::encode([0; NONCE_SIZE]);
+        assert!(matches!(tampered.decrypt_scoped(|_| unreachable!()).unwrap_err(), DecryptionError::Decryption));
+    }
+
+    #[test]
+    fn encrypt_for_recipients() {
+        let user_key = Secret::new(*b"0123456789abcdefghijklmnopqrstuv");
+        let recovery_key = TestConfigRandomized.keys().pop().unwrap();
+
+        let message = EncryptedMessage::<String, TestConfigRandomized>::encrypt_for_recipients("rigo does pretty codes".to_string(), &[user_key.clone(), recovery_key]).unwrap();
+        assert_eq!(message.headers.recipient_keys.len(), 2);
+        assert_eq!(message.encoded_strategy(), Some(StrategyKind::Randomized));
+        assert!(message.validate().is_ok());
+
+        // Test that each recipient can decrypt the message.
+        assert_eq!(message.decrypt_with_key(&user_key).unwrap(), "rigo does pretty codes");
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        assert_eq!(crate::rotate([message.to_string().parse().unwrap()], &TestConfigRandomized).next().unwrap().unwrap(), message);
+
+        // Test that other keys can't decrypt the message.
+        let other: EncryptedMessage<String, TestConfigCombinedTag> = message.to_string().parse().unwrap();
+        assert!(matches!(other.decrypt().unwrap_err(), DecryptionError::Decryption));
+
+        // Test that tampered recipient keys can't be unwrapped.
+        let mut tampered: EncryptedMessage<String, TestConfigRandomized> = message.to_string().parse().unwrap();
+        tampered.headers.recipient_keys[1] = base64::encode([0; WRAPPED_KEY_SIZE]);
+        assert!(matches!(tampered.decrypt().unwrap_err(), DecryptionError::Decryption));
+        assert_eq!(tampered.decrypt_with_key(&user_key).unwrap(), "rigo does pretty codes");
+
+        assert!(matches!(
+            EncryptedMessage::<String, TestConfigRandomized>::encrypt_for_recipients("rigo does pretty codes".to_string(), &[]).unwrap_err(),
+            EncryptionError::NoRecipients,
+        ));
+    }
+
+    #[test]
+    fn on_decrypt() {
+        use core::cell::Cell;
+
+        #[derive(Debug, Default)]
+        struct AuditedConfig {
+            attempts: Cell<(usize, usize)>,
+        }
+
+        impl Config for AuditedConfig {
+            type Strategy = crate::strategy::Randomized;
+
+            fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+                TestConfigRandomized.keys()
+            }
+
+            fn on_decrypt(&self, success: bool) {
+                let (successes, failures) = self.attempts.get();
+                self.attempts.set(if success { (successes + 1, failures) } else { (successes, failures + 1) });
+            }
+        }
+
+        let config = AuditedConfig::default();
+        let message = EncryptedMessage::<String, AuditedConfig>::encrypt_with_config("rigo does pretty codes".to_string(), &config).unwrap();
+        assert_eq!(message.decrypt_with_config(&config).unwrap(), "rigo does pretty codes");
+        assert!(message.verify_with_config(&config).is_ok());
+        assert_eq!(config.attempts.get(), (2, 0));
+
+        // Test that failed attempts are reported too.
+        let mut tampered: EncryptedMessage<String, AuditedConfig> = message.to_string().parse().unwrap();
+        tampered.headers.nonce = base64::encode([0; NONCE_SIZE]);
+        assert!(tampered.decrypt_with_config(&config).is_err());
+        assert_eq!(config.attempts.get(), (2, 1));
+
+        // Test that the hook is called for each message of a batch.
+        EncryptedMessage::decrypt_many_with_config(&[message, tampered], &config);
+        assert_eq!(config.attempts.get(), (3, 2));
+    }
+
+    #[test]
+    fn string_conversions() {
+        let json = r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA=="}}"#;
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::try_from(json.to_string()).unwrap();
+        assert_eq!(message.decrypt().unwrap(), "rigo does pretty codes");
+        assert_eq!(String::try_from(message).unwrap(), json);
+
+        // Test invalid JSON.
+        assert!(EncryptedMessage::<String, TestConfigDeterministic>::try_from("invalid".to_string()).is_err());
+    }
+
+    #[cfg(feature = "redact-debug")]
+    #[test]
+    fn redacted_debug() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(format!("{message:?}"), "EncryptedMessage { payload: <redacted 24 bytes>, .. }");
+    }
+
+    #[test]
+    fn display() {
+        let message = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap();
+        assert_eq!(
+            message.to_string(),
+            r#"{"p":"48lwH3W0sEJjjC3z4S8qyNVpdf6jN0sF","h":{"iv":"1WOXnWc3iX5iA3wdqMmcSeGEE365QXK0","at":"uXQhmffPV/1D7qG8stw6vA==","s":"d"}}"#,
+        );
+
+        // Test that the displayed form can be parsed back.
+        assert_eq!(message.to_string().parse::<EncryptedMessage<_, _>>().unwrap(), message);
+    }
+
+    #[test]
+    fn opaque_payload_type() {
+        // A payload type that can't be (de)serialized.
+        struct Opaque;
+
+        let json = EncryptedMessage::<String, TestConfigDeterministic>::encrypt("rigo does pretty codes".to_string()).unwrap().to_string();
+
+        // Test that messages can be parsed, validated, verified & written without knowing the payload type.
+        let message: EncryptedMessage<Opaque, TestConfigDeterministic> = json.parse().unwrap();
+        assert!(message.validate().is_ok());
+        assert!(message.verify().is_ok());
+        assert_eq!(message.to_string(), json);
+    }
 }
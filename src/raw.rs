@@ -0,0 +1,89 @@
+//! Lower-level functions encrypting & decrypting raw bytes, without a [`Config`](crate::config::Config) or payload serialization.
+//!
+//! This is useful for tools, like CLIs, where the payload type & configuration aren't known at compile time.
+//! The messages aren't [`EncryptedMessage`](crate::EncryptedMessage)s, & can't be decrypted as such.
+
+use alloc::vec::Vec;
+
+use chacha20poly1305::{AeadInPlace as _, KeyInit as _, XChaCha20Poly1305};
+use zeroize::Zeroizing;
+
+use crate::{FORMAT_VERSION, TAG_SIZE, error::DecryptionError, strategy::StrategyKind};
+
+/// Raw bytes encrypted with [`encrypt_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedMessageBytes {
+    /// The strategy the nonce was generated with.
+    pub strategy: StrategyKind,
+
+    /// The nonce used to encrypt the plaintext.
+    pub nonce: [u8; 24],
+
+    /// The encrypted plaintext, followed by the 16-byte auth tag.
+    pub ciphertext: Vec<u8>,
+}
+
+/// Encrypts the plaintext with XChaCha20Poly1305, using a nonce generated with the provided strategy.
+///
+/// The [`Deterministic`](crate::strategy::Deterministic) nonces are derived like in the latest version of the message format, see [`FORMAT_VERSION`].
+///
+/// # Panics
+///
+/// Panics if the strategy is [`StrategyKind::Counter`] & the `std` feature is disabled.
+pub fn encrypt_bytes(plaintext: &[u8], key: &[u8; 32], strategy: StrategyKind) -> EncryptedMessageBytes {
+    let nonce = strategy.generate_nonce_for_version(FORMAT_VERSION, plaintext, key, &[]);
+
+    let mut ciphertext = plaintext.to_vec();
+    let tag = XChaCha20Poly1305::new(key.into()).encrypt_in_place_detached(&nonce.into(), &[], &mut ciphertext).unwrap();
+    ciphertext.extend_from_slice(&tag);
+
+    EncryptedMessageBytes { strategy, nonce, ciphertext }
+}
+
+/// Decrypts raw bytes encrypted with [`encrypt_bytes`], returning the plaintext.
+///
+/// # Errors
+///
+/// Returns a [`DecryptionError::Decryption`] error if the ciphertext cannot be decrypted with the provided key.
+pub fn decrypt_bytes(message: &EncryptedMessageBytes, key: &[u8; 32]) -> Result<Zeroizing<Vec<u8>>, DecryptionError> {
+    let payload_len = message.ciphertext.len().checked_sub(TAG_SIZE).ok_or(DecryptionError::Decryption)?;
+    let (ciphertext, tag) = message.ciphertext.split_at(payload_len);
+
+    let mut plaintext = Zeroizing::new(ciphertext.to_vec());
+    XChaCha20Poly1305::new(key.into())
+        .decrypt_in_place_detached(&message.nonce.into(), &[], &mut plaintext, tag.into())
+        .map_err(|_| DecryptionError::Decryption)?;
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8; 32] = b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW";
+
+    #[test]
+    fn encrypts_and_decrypts_bytes() {
+        // The Counter strategy isn't tested here, as its process-global state is asserted on by `strategy::tests::counter`.
+        for strategy in [StrategyKind::Deterministic, StrategyKind::Randomized] {
+            let message = encrypt_bytes(b"rigo does pretty codes", KEY, strategy);
+            assert_eq!(message.strategy, strategy);
+            assert_eq!(message.ciphertext.len(), 22 + TAG_SIZE);
+            assert_eq!(*decrypt_bytes(&message, KEY).unwrap(), b"rigo does pretty codes");
+        }
+
+        // Test that the Deterministic strategy produces the same message for the same plaintext.
+        assert_eq!(encrypt_bytes(b"rigo", KEY, StrategyKind::Deterministic), encrypt_bytes(b"rigo", KEY, StrategyKind::Deterministic));
+        assert_ne!(encrypt_bytes(b"rigo", KEY, StrategyKind::Randomized), encrypt_bytes(b"rigo", KEY, StrategyKind::Randomized));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let mut message = encrypt_bytes(b"rigo does pretty codes", KEY, StrategyKind::Randomized);
+        assert!(matches!(decrypt_bytes(&message, b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").unwrap_err(), DecryptionError::Decryption));
+
+        message.ciphertext.truncate(TAG_SIZE - 1);
+        assert!(matches!(decrypt_bytes(&message, KEY).unwrap_err(), DecryptionError::Decryption));
+    }
+}
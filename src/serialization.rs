@@ -0,0 +1,173 @@
+//! The serialization formats that can be used to serialize payloads before encryption.
+
+use alloc::vec::Vec;
+use core::any::type_name;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::error::{EncryptionError, DecryptionError};
+
+/// The format used to serialize a payload before it's encrypted.
+///
+/// The format is stored in the headers of an [`EncryptedMessage`](crate::EncryptedMessage),
+/// so payloads are always deserialized with the format they were serialized with.
+/// New formats can be added by features, so matching on it requires a wildcard arm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Serialization {
+    /// Serializes payloads into JSON, using the [`serde_json`] crate.
+    #[default]
+    Json = 0,
+
+    /// Serializes payloads into CBOR, using the [`ciborium`](https://docs.rs/ciborium) crate.
+    #[cfg(feature = "cbor")]
+    Cbor = 1,
+
+    /// Serializes payloads into the compact [Postcard](https://postcard.jamesmunns.com) format, using the
+    /// [`postcard`](https://docs.rs/postcard) crate, which supports `no_std`.
+    ///
+    /// Postcard isn't self-describing, so payloads must be deserialized into the type they were serialized from,
+    /// & types deserialized from any value, like [`serde_json::Value`], aren't supported. For the same reason,
+    /// empty payloads can't be told apart from zeroes, so [`Config::reject_empty`](crate::config::Config::reject_empty) doesn't apply.
+    #[cfg(feature = "postcard")]
+    Postcard = 2,
+}
+
+impl Serialization {
+    /// Returns `true` if the format is [`Serialization::Json`].
+    pub(crate) fn is_json(&self) -> bool {
+        *self == Serialization::Json
+    }
+
+    /// Returns `true` if the serialized payload is an empty string, byte string, sequence or map.
+    pub(crate) fn is_empty_value(self, bytes: &[u8]) -> bool {
+        match self {
+            Serialization::Json => matches!(bytes, b"\"\"" | b"[]" | b"{}"),
+            // Major types 2 to 5 with a length of 0.
+            #[cfg(feature = "cbor")]
+            Serialization::Cbor => matches!(bytes, [0x40 | 0x60 | 0x80 | 0xa0]),
+            // An empty string, sequence or map is a length of 0, like `0`, `false` & `None`.
+            #[cfg(feature = "postcard")]
+            Serialization::Postcard => false,
+        }
+    }
+
+    /// Returns the stable identifier of the format, used in the binary representation of messages & their associated data.
+    pub(crate) fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the format with the provided identifier, if it's supported.
+    #[cfg(feature = "bincode")]
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Serialization::Json),
+            #[cfg(feature = "cbor")]
+            1 => Some(Serialization::Cbor),
+            #[cfg(feature = "postcard")]
+            2 => Some(Serialization::Postcard),
+            _ => None,
+        }
+    }
+
+    /// Serializes a payload into bytes.
+    pub(crate) fn serialize<T: Serialize + ?Sized>(self, payload: &T) -> Result<Vec<u8>, EncryptionError> {
+        match self {
+            Serialization::Json => serde_json::to_vec(payload).map_err(|error| EncryptionError::Serialization(error.into())),
+            #[cfg(feature = "cbor")]
+            Serialization::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(payload, &mut buffer).map_err(|error| EncryptionError::Serialization(error.into()))?;
+
+                Ok(buffer)
+            },
+            #[cfg(feature = "postcard")]
+            Serialization::Postcard => postcard::to_allocvec(payload).map_err(|error| EncryptionError::Serialization(error.into())),
+        }
+    }
+
+    /// Deserializes a payload from bytes.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, DecryptionError> {
+        match self {
+            Serialization::Json => serde_json::from_slice(bytes).map_err(|error| DecryptionError::Deserialization {
+                type_name: type_name::<T>(),
+                source: error.into(),
+            }),
+            #[cfg(feature = "cbor")]
+            Serialization::Cbor => ciborium::from_reader(bytes).map_err(|error| DecryptionError::Deserialization {
+                type_name: type_name::<T>(),
+                source: error.into(),
+            }),
+            #[cfg(feature = "postcard")]
+            Serialization::Postcard => postcard::from_bytes(bytes).map_err(|error| DecryptionError::Deserialization {
+                type_name: type_name::<T>(),
+                source: error.into(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_is_default() {
+        assert_eq!(Serialization::default(), Serialization::Json);
+        assert!(Serialization::default().is_json());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let bytes = Serialization::Json.serialize(&vec![1, 2, 3]).unwrap();
+        assert_eq!(bytes, b"[1,2,3]");
+        assert_eq!(Serialization::Json.deserialize::<Vec<u8>>(&bytes).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn detects_empty_values() {
+        assert!(Serialization::Json.is_empty_value(b"\"\""));
+        assert!(Serialization::Json.is_empty_value(&Serialization::Json.serialize(&Vec::<u8>::new()).unwrap()));
+        assert!(!Serialization::Json.is_empty_value(b"null"));
+        assert!(!Serialization::Json.is_empty_value(b"\" \""));
+
+        #[cfg(feature = "cbor")]
+        {
+            assert!(Serialization::Cbor.is_empty_value(&Serialization::Cbor.serialize("").unwrap()));
+            assert!(Serialization::Cbor.is_empty_value(&Serialization::Cbor.serialize(&alloc::collections::BTreeMap::<u8, u8>::new()).unwrap()));
+            assert!(!Serialization::Cbor.is_empty_value(&Serialization::Cbor.serialize(&Option::<u8>::None).unwrap()));
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn ids_are_stable() {
+        assert_eq!(Serialization::Json.id(), 0);
+        assert_eq!(Serialization::from_id(0), Some(Serialization::Json));
+        assert_eq!(Serialization::from_id(u8::MAX), None);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let bytes = Serialization::Cbor.serialize(&vec![1, 2, 3]).unwrap();
+        assert_eq!(bytes, [0x83, 0x01, 0x02, 0x03]);
+        assert_eq!(Serialization::Cbor.deserialize::<Vec<u8>>(&bytes).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard_round_trip() {
+        let bytes = Serialization::Postcard.serialize(&vec![1_u8, 2, 3]).unwrap();
+        assert_eq!(bytes, [0x03, 0x01, 0x02, 0x03]);
+        assert_eq!(Serialization::Postcard.deserialize::<Vec<u8>>(&bytes).unwrap(), vec![1, 2, 3]);
+
+        // Test that truncated payloads are rejected.
+        assert!(Serialization::Postcard.deserialize::<Vec<u8>>(&bytes[..2]).is_err());
+
+        // Test that empty values aren't detected, as they can't be told apart from zeroes.
+        assert_eq!(Serialization::Postcard.serialize("").unwrap(), Serialization::Postcard.serialize(&false).unwrap());
+        assert!(!Serialization::Postcard.is_empty_value(&Serialization::Postcard.serialize("").unwrap()));
+    }
+}
@@ -1,20 +1,105 @@
 //! All the encryption strategies that can be used with [`EncryptedMessage`](crate::EncryptedMessage).
 
-use std::fmt::Debug;
+use alloc::vec::Vec;
+use core::{fmt::{self, Debug, Display, Formatter}, marker::PhantomData};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
 
+use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use zeroize::Zeroizing;
+
+#[cfg(feature = "std")]
+use crate::error::CounterError;
+use crate::utilities::random;
 
 mod private {
     pub trait Sealed {}
 
     impl Sealed for super::Deterministic {}
-    impl Sealed for super::Randomized {}
+    impl<S: super::NonceSource> Sealed for super::Randomized<S> {}
+    #[cfg(feature = "std")]
+    impl Sealed for super::Counter {}
+}
+
+/// Identifies the strategy a message was encrypted with.
+///
+/// It's stored in the headers of an [`EncryptedMessage`](crate::EncryptedMessage) as a single character,
+/// & can be retrieved with [`EncryptedMessage::encoded_strategy`](crate::EncryptedMessage::encoded_strategy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StrategyKind {
+    /// The [`Deterministic`] strategy.
+    #[serde(rename = "d")]
+    Deterministic,
+
+    /// The [`Randomized`] strategy.
+    #[serde(rename = "r")]
+    Randomized,
+
+    /// The `Counter` strategy.
+    #[serde(rename = "c")]
+    Counter,
+}
+
+impl StrategyKind {
+    /// Returns the lowercase name of the strategy, e.g. `randomized`, to use in logs & metrics.
+    pub const fn name(self) -> &'static str {
+        match self {
+            StrategyKind::Deterministic => "deterministic",
+            StrategyKind::Randomized => "randomized",
+            StrategyKind::Counter => "counter",
+        }
+    }
+
+    /// Generates a nonce with the strategy of this kind, see [`Strategy::generate_nonce_for_version`].
+    /// The [`Randomized`] strategy uses the default [`NonceSource`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the kind is [`StrategyKind::Counter`] & the `std` feature is disabled.
+    pub(crate) fn generate_nonce_for_version(self, version: u8, payload: &[u8], key: &[u8; 32], context: &[u8]) -> [u8; 24] {
+        match self {
+            StrategyKind::Deterministic => Deterministic::generate_nonce_for_version(version, payload, key, context),
+            StrategyKind::Randomized => <Randomized>::generate_nonce_for_version(version, payload, key, context),
+            #[cfg(feature = "std")]
+            StrategyKind::Counter => Counter::generate_nonce_for_version(version, payload, key, context),
+            #[cfg(not(feature = "std"))]
+            StrategyKind::Counter => panic!("The Counter strategy requires the `std` feature."),
+        }
+    }
+}
+
+impl Display for StrategyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 pub trait Strategy: private::Sealed + Debug {
+    /// The kind of the strategy, stored in the headers of encrypted messages.
+    const KIND: StrategyKind;
+
+    /// The lowercase name of the strategy, e.g. `randomized`, see [`StrategyKind::name`].
+    const NAME: &'static str = Self::KIND.name();
+
     /// Generates a 192-bit nonce to encrypt a payload.
-    fn generate_nonce_for(payload: &[u8], key: &[u8; 32]) -> [u8; 24];
+    ///
+    /// The context is provided by [`Config::context`](crate::config::Config::context), combined with the context
+    /// passed to [`EncryptedMessage::encrypt_with_context_and_config`](crate::EncryptedMessage::encrypt_with_context_and_config) if any,
+    /// & allows strategies to scope their nonces, e.g. to a specific column.
+    fn generate_nonce_for(payload: &[u8], key: &[u8; 32], context: &[u8]) -> [u8; 24];
+
+    /// Generates a 192-bit nonce to encrypt a payload, using the derivation of the provided message format version,
+    /// see [`Config::format_version`](crate::config::Config::format_version).
+    ///
+    /// Defaults to [`Strategy::generate_nonce_for`], as only the derivation of [`Deterministic`] nonces depends on the version.
+    fn generate_nonce_for_version(version: u8, payload: &[u8], key: &[u8; 32], context: &[u8]) -> [u8; 24] {
+        let _ = version;
+
+        Self::generate_nonce_for(payload, key, context)
+    }
 }
 
 /// This encryption strategy is guaranteed to always produce the same nonce for a payload,
@@ -22,16 +107,51 @@ pub trait Strategy: private::Sealed + Debug {
 ///
 /// This is useful for data you'd like to be able to query, as you can simply encrypt
 /// the payload you're querying for & search for the same encrypted message.
+///
+/// When a non-empty context is provided, the nonce is generated with a key derived from the context,
+/// so the same payload produces different encrypted messages under different contexts.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Deterministic;
 impl Strategy for Deterministic {
-    /// Generates a deterministic 192-bit nonce for the payload.
-    fn generate_nonce_for(payload: &[u8], key: &[u8; 32]) -> [u8; 24] {
-        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    const KIND: StrategyKind = StrategyKind::Deterministic;
+
+    /// Generates a deterministic 192-bit nonce for the payload, scoped to the context.
+    fn generate_nonce_for(payload: &[u8], key: &[u8; 32], context: &[u8]) -> [u8; 24] {
+        // Messages without a context use the key directly, so their nonces stay the same.
+        let mac_key = if context.is_empty() {
+            Zeroizing::new(*key)
+        } else {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+            mac.update(b"encrypted-message context");
+            mac.update(context);
+
+            Zeroizing::new(mac.finalize().into_bytes().into())
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(mac_key.as_slice()).unwrap();
         mac.update(payload);
 
         mac.finalize().into_bytes()[0..24].try_into().unwrap()
     }
+
+    /// From version 1, the nonce is derived from the key, context & payload with HKDF-SHA256,
+    /// using a domain-separation label as salt. Version 0 uses [`Deterministic::generate_nonce_for`].
+    fn generate_nonce_for_version(version: u8, payload: &[u8], key: &[u8; 32], context: &[u8]) -> [u8; 24] {
+        if version == 0 {
+            return Self::generate_nonce_for(payload, key, context);
+        }
+
+        // The context is length-prefixed, so different contexts & payloads never produce the same input.
+        let mut info = Zeroizing::new(Vec::with_capacity(8 + context.len() + payload.len()));
+        info.extend_from_slice(&(context.len() as u64).to_be_bytes());
+        info.extend_from_slice(context);
+        info.extend_from_slice(payload);
+
+        let mut nonce = [0; 24];
+        Hkdf::<Sha256>::new(Some(b"encrypted-message deterministic nonce"), key).expand(&info, &mut nonce).unwrap();
+
+        nonce
+    }
 }
 
 /// This encryption strategy will produce a random nonce, regardless of the payload,
@@ -39,12 +159,128 @@ impl Strategy for Deterministic {
 ///
 /// This encryption strategy improves security by making crypto-analysis of encrypted messages harder,
 /// but makes querying them without decrypting all data impossible.
+///
+/// Nonces are generated by a [`NonceSource`], which defaults to the operating system's random number generator.
+/// A different source can be provided, e.g. a seeded one to produce reproducible test fixtures.
 #[derive(Debug, PartialEq, Eq)]
-pub struct Randomized;
-impl Strategy for Randomized {
-    /// Generates a random 192-bit nonce for the payload.
-    fn generate_nonce_for(_payload: &[u8], _key: &[u8; 32]) -> [u8; 24] {
-        rand::random()
+pub struct Randomized<S = OsSource>(PhantomData<S>);
+impl<S: NonceSource> Strategy for Randomized<S> {
+    const KIND: StrategyKind = StrategyKind::Randomized;
+
+    /// Generates a random 192-bit nonce for the payload, using the [`NonceSource`].
+    fn generate_nonce_for(_payload: &[u8], _key: &[u8; 32], _context: &[u8]) -> [u8; 24] {
+        S::generate_nonce()
+    }
+}
+
+/// A source of random nonces for the [`Randomized`] strategy.
+///
+/// Implementations must return unpredictable nonces in production, as reusing a nonce with the same key
+/// breaks the security of the encryption.
+pub trait NonceSource: Debug {
+    /// Generates a random 192-bit nonce.
+    fn generate_nonce() -> [u8; 24];
+}
+
+/// The default [`NonceSource`], using a cryptographically secure random number generator
+/// seeded by the operating system.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OsSource;
+impl NonceSource for OsSource {
+    fn generate_nonce() -> [u8; 24] {
+        random::random()
+    }
+}
+
+/// The state of the [`Counter`] strategy, which is shared across the process.
+#[cfg(feature = "std")]
+static COUNTER_STATE: Mutex<Option<CounterState>> = Mutex::new(None);
+
+/// The prefix & counter used for the next nonce, & whether a nonce was generated in this process.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+struct CounterState {
+    prefix: [u8; 16],
+    counter: u64,
+    used: bool,
+}
+
+#[cfg(feature = "std")]
+impl CounterState {
+    /// Returns a state with a new random prefix & a counter starting at `0`.
+    fn random(used: bool) -> Self {
+        Self { prefix: random::random(), counter: 0, used }
+    }
+}
+
+/// This encryption strategy will produce a nonce made of a random 128-bit prefix,
+/// generated once per process, followed by a 64-bit monotonic counter.
+/// Like [`Randomized`], it generates a different encrypted message every time.
+///
+/// Unlike [`Randomized`], nonces are guaranteed to be unique within a process run,
+/// instead of being statistically unlikely to collide. If you'd like nonces to stay unique across restarts
+/// without relying on a new random prefix, **reserve counters ahead of their use**: persist the prefix with
+/// the counter returned by [`Counter::state`] plus the number of nonces to reserve, `N`, before generating them,
+/// & reserve the next `N` before the counter reaches the persisted value. On restart, restore the persisted value
+/// with [`Counter::seed`], before any nonce is generated. Persisting the current counter instead reuses nonces
+/// if the process stops after using counters past the persisted value.
+///
+/// **A persisted state must only ever be restored by a single process.** Processes restoring the same state
+/// generate the same nonces, so each process must persist its own state, or start with a random prefix.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Counter;
+#[cfg(feature = "std")]
+impl Counter {
+    /// Seeds the prefix & counter used for the following nonces, e.g. with a state reserved ahead of its use,
+    /// see [`Counter`].
+    ///
+    /// # Errors
+    ///
+    /// Once a nonce has been generated, returns a [`CounterError::Backwards`] error if the prefix changes, or the counter
+    /// is smaller than the one returned by [`Counter::state`], since the nonces in between may have been used already.
+    pub fn seed(prefix: [u8; 16], counter: u64) -> Result<(), CounterError> {
+        let mut state = COUNTER_STATE.lock().unwrap_or_else(PoisonError::into_inner);
+        let used = state.is_some_and(|state| state.used);
+        if state.is_some_and(|state| used && (state.prefix != prefix || state.counter > counter)) {
+            return Err(CounterError::Backwards);
+        }
+
+        *state = Some(CounterState { prefix, counter, used });
+
+        Ok(())
+    }
+
+    /// Returns the prefix & counter that will be used for the next nonce,
+    /// generating a random prefix if none has been seeded or used yet.
+    pub fn state() -> ([u8; 16], u64) {
+        let state = *COUNTER_STATE.lock().unwrap_or_else(PoisonError::into_inner).get_or_insert_with(|| CounterState::random(false));
+
+        (state.prefix, state.counter)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Strategy for Counter {
+    const KIND: StrategyKind = StrategyKind::Counter;
+
+    /// Generates a unique 192-bit nonce for the payload, from the prefix & the next counter value.
+    fn generate_nonce_for(_payload: &[u8], _key: &[u8; 32], _context: &[u8]) -> [u8; 24] {
+        let mut state = COUNTER_STATE.lock().unwrap_or_else(PoisonError::into_inner);
+        let CounterState { prefix, counter, used } = state.get_or_insert_with(|| CounterState::random(false));
+        *used = true;
+
+        let mut nonce = [0; 24];
+        nonce[..16].copy_from_slice(prefix);
+        nonce[16..].copy_from_slice(&counter.to_be_bytes());
+
+        // Start over with a new random prefix in the unlikely event the counter is exhausted.
+        match counter.checked_add(1) {
+            Some(next) => *counter = next,
+            None => *state = Some(CounterState::random(true)),
+        }
+
+        nonce
     }
 }
 
@@ -56,17 +292,28 @@ mod tests {
 
     use crate::{
         config::Config as _,
-        testing::{TestConfigDeterministic, TestConfigRandomized},
+        testing::{SeededSource, TestConfigDeterministic, TestConfigRandomized},
         utilities::base64,
     };
 
+    #[test]
+    fn names() {
+        assert_eq!(Deterministic::NAME, "deterministic");
+        assert_eq!(Randomized::<SeededSource>::NAME, "randomized");
+        #[cfg(feature = "std")]
+        assert_eq!(Counter::NAME, "counter");
+
+        // Test that kinds are displayed by name, e.g. when logging the strategy of stored messages.
+        assert_eq!(StrategyKind::Randomized.to_string(), "randomized");
+    }
+
     mod deterministic {
         use super::*;
 
         #[test]
         fn nonce_is_deterministic() {
             let key = TestConfigDeterministic.primary_key();
-            let nonce = Deterministic::generate_nonce_for("rigo is cool".as_bytes(), key.expose_secret());
+            let nonce = Deterministic::generate_nonce_for("rigo is cool".as_bytes(), key.expose_secret(), b"");
 
             // Test that the nonce is 24 bytes long.
             assert_eq!(nonce.len(), 24);
@@ -74,6 +321,37 @@ mod tests {
             // Test that the nonce is deterministic.
             assert_eq!(nonce, *base64::decode("Ts2jGkMEW9NFsQZXO+2BA60uExH5xfEe").unwrap());
         }
+
+        #[test]
+        fn nonce_is_scoped_to_context() {
+            let key = TestConfigDeterministic.primary_key();
+            let payload = "rigo is cool".as_bytes();
+            let nonce = Deterministic::generate_nonce_for(payload, key.expose_secret(), b"users.email");
+
+            // Test that the nonce is deterministic within the context.
+            assert_eq!(nonce, Deterministic::generate_nonce_for(payload, key.expose_secret(), b"users.email"));
+
+            // Test that the nonce is different across contexts.
+            assert_ne!(nonce, Deterministic::generate_nonce_for(payload, key.expose_secret(), b"users.name"));
+            assert_ne!(nonce, Deterministic::generate_nonce_for(payload, key.expose_secret(), b""));
+        }
+
+        #[test]
+        fn nonce_derivation_depends_on_version() {
+            let key = TestConfigDeterministic.primary_key();
+            let payload = "rigo is cool".as_bytes();
+
+            // Test that version 0 uses the initial derivation, so existing messages still match.
+            let initial_nonce = Deterministic::generate_nonce_for_version(0, payload, key.expose_secret(), b"");
+            assert_eq!(initial_nonce, Deterministic::generate_nonce_for(payload, key.expose_secret(), b""));
+
+            // Test that version 1 uses HKDF, & is still deterministic & scoped to the context.
+            let nonce = Deterministic::generate_nonce_for_version(1, payload, key.expose_secret(), b"");
+            assert_ne!(nonce, initial_nonce);
+            assert_eq!(nonce, *base64::decode("3tH/lJxp4kU2WOWQGbx0NfRFNWVW/1P5").unwrap());
+            assert_eq!(nonce, Deterministic::generate_nonce_for_version(1, payload, key.expose_secret(), b""));
+            assert_ne!(nonce, Deterministic::generate_nonce_for_version(1, payload, key.expose_secret(), b"users.email"));
+        }
     }
 
     mod randomized {
@@ -83,8 +361,8 @@ mod tests {
         fn nonce_is_randomized() {
             let payload = "much secret much secure".as_bytes();
             let key = TestConfigRandomized.primary_key();
-            let first_nonce = Randomized::generate_nonce_for(payload, key.expose_secret());
-            let second_nonce = Randomized::generate_nonce_for(payload, key.expose_secret());
+            let first_nonce = <Randomized>::generate_nonce_for(payload, key.expose_secret(), b"");
+            let second_nonce = <Randomized>::generate_nonce_for(payload, key.expose_secret(), b"");
 
             // Test that the nonces are 24 bytes long.
             assert_eq!(first_nonce.len(), 24);
@@ -93,5 +371,58 @@ mod tests {
             // Test that the nonces never match, even when generated for the same payload.
             assert_ne!(first_nonce, second_nonce);
         }
+
+        #[test]
+        fn nonce_uses_source() {
+            let payload = "much secret much secure".as_bytes();
+            let key = TestConfigRandomized.primary_key();
+
+            SeededSource::seed(0);
+            let first_nonce = Randomized::<SeededSource>::generate_nonce_for(payload, key.expose_secret(), b"");
+
+            // Test that the same seed produces the same nonces.
+            SeededSource::seed(0);
+            assert_eq!(Randomized::<SeededSource>::generate_nonce_for(payload, key.expose_secret(), b""), first_nonce);
+            assert_ne!(Randomized::<SeededSource>::generate_nonce_for(payload, key.expose_secret(), b""), first_nonce);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    mod counter {
+        use super::*;
+
+        #[test]
+        fn nonce_is_unique() {
+            let payload = "much secret much secure".as_bytes();
+            let key = TestConfigRandomized.primary_key();
+
+            Counter::seed([7; 16], 41).unwrap();
+            assert_eq!(Counter::state(), ([7; 16], 41));
+
+            let first_nonce = Counter::generate_nonce_for(payload, key.expose_secret(), b"");
+            let second_nonce = Counter::generate_nonce_for(payload, key.expose_secret(), b"");
+
+            // Test that the nonces are made of the prefix & the counter.
+            assert_eq!(first_nonce[..16], [7; 16]);
+            assert_eq!(first_nonce[16..], 41_u64.to_be_bytes());
+            assert_eq!(second_nonce[..16], [7; 16]);
+            assert_eq!(second_nonce[16..], 42_u64.to_be_bytes());
+            assert_eq!(Counter::state(), ([7; 16], 43));
+
+            // Test that the counter can't be moved backwards, or to another prefix, once it has been used.
+            assert!(matches!(Counter::seed([7; 16], 42).unwrap_err(), CounterError::Backwards));
+            assert!(matches!(Counter::seed([8; 16], 43).unwrap_err(), CounterError::Backwards));
+            assert_eq!(Counter::state(), ([7; 16], 43));
+            Counter::seed([7; 16], 43).unwrap();
+            Counter::seed([7; 16], 100).unwrap();
+            assert_eq!(Counter::state(), ([7; 16], 100));
+
+            // Test that the prefix is regenerated when the counter is exhausted.
+            Counter::seed([7; 16], u64::MAX).unwrap();
+            let last_nonce = Counter::generate_nonce_for(payload, key.expose_secret(), b"");
+            assert_eq!(last_nonce[16..], u64::MAX.to_be_bytes());
+            assert_ne!(Counter::state(), ([7; 16], 0));
+            assert_eq!(Counter::state().1, 0);
+        }
     }
 }
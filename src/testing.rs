@@ -1,8 +1,64 @@
-use crate::{config::{Config, Secret}, strategy::{Deterministic, Randomized}};
+use alloc::{vec, vec::Vec};
+use core::{cell::RefCell, num::NonZeroUsize};
+
+use rand::{RngCore as _, SeedableRng as _};
+use rand_chacha::ChaCha20Rng;
+
+use crate::{config::{Config, Secret}, strategy::{Deterministic, NonceSource, Randomized}};
+pub use crate::fixtures::{TestConfigDeterministic, TestConfigRandomized};
+#[cfg(any(feature = "cbor", feature = "postcard"))]
+use crate::serialization::Serialization;
+
+std::thread_local! {
+    static SEEDED_RNG: RefCell<ChaCha20Rng> = RefCell::new(ChaCha20Rng::seed_from_u64(0));
+}
+
+/// A [`NonceSource`] using a seeded random number generator, to produce reproducible nonces in tests.
+/// Each test thread has its own generator.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SeededSource;
+impl SeededSource {
+    pub fn seed(seed: u64) {
+        SEEDED_RNG.with(|rng| *rng.borrow_mut() = ChaCha20Rng::seed_from_u64(seed));
+    }
+}
+
+impl NonceSource for SeededSource {
+    fn generate_nonce() -> [u8; 24] {
+        let mut nonce = [0; 24];
+        SEEDED_RNG.with(|rng| rng.borrow_mut().fill_bytes(&mut nonce));
+
+        nonce
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigSeeded;
+impl Config for TestConfigSeeded {
+    type Strategy = Randomized<SeededSource>;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigCombinedTag;
+impl Config for TestConfigCombinedTag {
+    type Strategy = Deterministic;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn combined_tag(&self) -> bool {
+        true
+    }
+}
 
 #[derive(Debug, Default, PartialEq, Eq)]
-pub struct TestConfigDeterministic;
-impl Config for TestConfigDeterministic {
+pub struct TestConfigHmacTag;
+impl Config for TestConfigHmacTag {
     type Strategy = Deterministic;
 
     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
@@ -11,17 +67,96 @@ impl Config for TestConfigDeterministic {
             (*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into(),
         ]
     }
+
+    fn hmac_tag(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigLatestFormat;
+impl Config for TestConfigLatestFormat {
+    type Strategy = Deterministic;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        TestConfigDeterministic.keys()
+    }
+
+    fn format_version(&self) -> u8 {
+        crate::FORMAT_VERSION
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
-pub struct TestConfigRandomized;
-impl Config for TestConfigRandomized {
+pub struct TestConfigPadded;
+impl Config for TestConfigPadded {
+    type Strategy = Deterministic;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn pad_to(&self) -> Option<NonZeroUsize> {
+        NonZeroUsize::new(16)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigLimited;
+impl Config for TestConfigLimited {
     type Strategy = Randomized;
 
     fn keys(&self) -> Vec<Secret<[u8; 32]>> {
-        vec![
-            (*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into(),
-            (*b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt").into(),
-        ]
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn max_plaintext_len(&self) -> Option<usize> {
+        Some(24)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigTimestamped;
+impl Config for TestConfigTimestamped {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn stamp_time(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigCbor;
+#[cfg(feature = "cbor")]
+impl Config for TestConfigCbor {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn serialization(&self) -> Serialization {
+        Serialization::Cbor
+    }
+}
+
+#[cfg(feature = "postcard")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TestConfigPostcard;
+#[cfg(feature = "postcard")]
+impl Config for TestConfigPostcard {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+
+    fn serialization(&self) -> Serialization {
+        Serialization::Postcard
     }
 }
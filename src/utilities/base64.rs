@@ -1,3 +1,5 @@
+use alloc::{string::String, vec::Vec};
+
 use base64::{engine::general_purpose, DecodeError, Engine as _};
 
 pub fn encode<T: AsRef<[u8]>>(input: T) -> String {
@@ -7,3 +9,7 @@ pub fn encode<T: AsRef<[u8]>>(input: T) -> String {
 pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>, DecodeError> {
     general_purpose::STANDARD.decode(input)
 }
+
+pub fn decode_into<T: AsRef<[u8]>>(input: T, buffer: &mut Vec<u8>) -> Result<(), DecodeError> {
+    general_purpose::STANDARD.decode_vec(input, buffer)
+}
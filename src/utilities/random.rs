@@ -0,0 +1,15 @@
+use rand::distributions::{Distribution, Standard};
+
+/// Generates a random value using a cryptographically secure random number generator.
+///
+/// Uses the thread-local generator with the `std` feature, & the operating system's generator otherwise.
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    #[cfg(feature = "std")]
+    return rand::random();
+
+    #[cfg(not(feature = "std"))]
+    return rand::Rng::gen(&mut rand::rngs::OsRng);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "derive")]
+
+use encrypted_message::{
+    EncryptedFields,
+    EncryptedMessage,
+    strategy::Randomized,
+    config::{Config, Secret},
+};
+
+#[derive(Debug, Default)]
+struct EncryptionConfig;
+impl Config for EncryptionConfig {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+}
+
+#[derive(Debug)]
+struct KeyConfig {
+    key: [u8; 32],
+}
+impl Config for KeyConfig {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![self.key.into()]
+    }
+}
+
+#[derive(EncryptedFields)]
+struct User {
+    #[encrypted]
+    diary: EncryptedMessage<String, EncryptionConfig>,
+    #[encrypted]
+    lucky_numbers: Option<EncryptedMessage<Vec<u8>, EncryptionConfig>>,
+}
+
+#[derive(EncryptedFields)]
+struct Account {
+    config: KeyConfig,
+    #[encrypted(config = self.config)]
+    notes: EncryptedMessage<String, KeyConfig>,
+    #[encrypted(config = self.config)]
+    pin: Option<EncryptedMessage<u32, KeyConfig>>,
+}
+
+#[test]
+fn encrypted_fields() {
+    let mut user = User {
+        diary: EncryptedMessage::encrypt("Very secret.".to_string()).unwrap(),
+        lucky_numbers: None,
+    };
+    assert_eq!(user.decrypt_diary().unwrap(), "Very secret.");
+    assert_eq!(user.decrypt_lucky_numbers().unwrap(), None);
+
+    user.encrypt_diary("Even more secret.".to_string()).unwrap();
+    user.encrypt_lucky_numbers(vec![7, 13]).unwrap();
+    assert_eq!(user.decrypt_diary().unwrap(), "Even more secret.");
+    assert_eq!(user.decrypt_lucky_numbers().unwrap(), Some(vec![7, 13]));
+}
+
+#[test]
+fn encrypted_fields_with_config() {
+    let config = KeyConfig { key: *b"tiwQCWKCsW1d6qzZfp7HYvnRqZPYYhMt" };
+    let notes = EncryptedMessage::encrypt_with_config("Very secret.".to_string(), &config).unwrap();
+    let mut account = Account { config, notes, pin: None };
+    assert_eq!(account.decrypt_notes().unwrap(), "Very secret.");
+    assert_eq!(account.decrypt_pin().unwrap(), None);
+
+    account.encrypt_notes("Even more secret.".to_string()).unwrap();
+    account.encrypt_pin(1234).unwrap();
+    assert_eq!(account.decrypt_notes().unwrap(), "Even more secret.");
+    assert_eq!(account.decrypt_pin().unwrap(), Some(1234));
+}
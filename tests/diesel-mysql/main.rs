@@ -62,8 +62,32 @@ fn encrypted_message_works() {
         .unwrap();
 
     // Load the new user from the database.
-    let user: User = schema::users::table.find(&id).first(&mut connection).unwrap();
+    let user: User = schema::users::table.find(&id).select(User::as_select()).first(&mut connection).unwrap();
 
     // Decrypt the user's secrets.
     assert_eq!(user.json.as_ref().unwrap().decrypt().unwrap(), "Very secret.");
 }
+
+#[cfg(feature = "diesel-binary")]
+#[test]
+fn binary_encrypted_message_works() {
+    // Attempt to load environment variables from .env.test
+    let _ = dotenvy::from_filename(".env.test");
+
+    let database_url = dotenvy::var("MYSQL_DATABASE_URL").expect("MYSQL_DATABASE_URL must be set.");
+    let mut connection = MysqlConnection::establish(&database_url).unwrap();
+
+    // Create a new user, with a secret stored in its binary representation.
+    let id = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(schema::users::table)
+        .values((
+            schema::users::id.eq(&id),
+            schema::users::blob.eq(Some(EncryptedMessage::<String, EncryptionConfig>::encrypt("Very secret, & compact.".to_string()).unwrap())),
+        ))
+        .execute(&mut connection)
+        .unwrap();
+
+    // Load & decrypt the user's secret.
+    let secret: Option<EncryptedMessage<String, EncryptionConfig>> = schema::users::table.find(&id).select(schema::users::blob).first(&mut connection).unwrap();
+    assert_eq!(secret.unwrap().decrypt().unwrap(), "Very secret, & compact.");
+}
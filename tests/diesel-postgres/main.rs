@@ -59,6 +59,7 @@ fn encrypted_message_works() {
             json: Some(EncryptedMessage::encrypt("Very secret.".to_string()).unwrap()),
             jsonb: Some(EncryptedMessage::encrypt("Very secret, also binary.".to_string()).unwrap()),
         })
+        .returning(User::as_returning())
         .get_result(&mut connection)
         .unwrap();
 
@@ -66,3 +67,23 @@ fn encrypted_message_works() {
     assert_eq!(user.json.as_ref().unwrap().decrypt().unwrap(), "Very secret.");
     assert_eq!(user.jsonb.as_ref().unwrap().decrypt().unwrap(), "Very secret, also binary.");
 }
+
+#[cfg(feature = "diesel-binary")]
+#[test]
+fn binary_encrypted_message_works() {
+    // Attempt to load environment variables from .env.test
+    let _ = dotenvy::from_filename(".env.test");
+
+    let database_url = dotenvy::var("POSTGRES_DATABASE_URL").expect("POSTGRES_DATABASE_URL must be set.");
+    let mut connection = PgConnection::establish(&database_url).unwrap();
+
+    // Create a new user, with a secret stored in its binary representation.
+    let secret: Option<EncryptedMessage<String, EncryptionConfig>> = diesel::insert_into(schema::users::table)
+        .values(schema::users::bytea.eq(Some(EncryptedMessage::<String, EncryptionConfig>::encrypt("Very secret, & compact.".to_string()).unwrap())))
+        .returning(schema::users::bytea)
+        .get_result(&mut connection)
+        .unwrap();
+
+    // Decrypt the user's secret.
+    assert_eq!(secret.unwrap().decrypt().unwrap(), "Very secret, & compact.");
+}
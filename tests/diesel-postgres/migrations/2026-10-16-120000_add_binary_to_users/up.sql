@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN bytea BYTEA;
@@ -0,0 +1,102 @@
+#![cfg(all(feature = "diesel", feature = "diesel-sqlite"))]
+
+mod schema;
+
+use diesel::prelude::*;
+use encrypted_message::{
+    EncryptedMessage,
+    strategy::Randomized,
+    config::{Config, Secret},
+};
+
+#[derive(Debug, Default)]
+struct EncryptionConfig;
+impl Config for EncryptionConfig {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        vec![(*b"uuOxfpWgRgIEo3dIrdo0hnHJHF1hntvW").into()]
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = schema::users)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct User {
+    #[allow(dead_code)]
+    id: String,
+    json: Option<EncryptedMessage<String, EncryptionConfig>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::users)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct UserInsertable {
+    id: String,
+    json: Option<EncryptedMessage<String, EncryptionConfig>>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = schema::users)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct UserChangeset {
+    json: Option<Option<EncryptedMessage<String, EncryptionConfig>>>,
+}
+
+#[test]
+fn encrypted_message_works() {
+    let mut connection = SqliteConnection::establish(":memory:").unwrap();
+    diesel::sql_query("CREATE TABLE users (id TEXT PRIMARY KEY NOT NULL, json TEXT, binary BLOB)")
+        .execute(&mut connection)
+        .unwrap();
+
+    // Create a new user.
+    let id = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(schema::users::table)
+        .values(UserInsertable {
+            id: id.clone(),
+            json: Some(EncryptedMessage::encrypt("Very secret.".to_string()).unwrap()),
+        })
+        .execute(&mut connection)
+        .unwrap();
+
+    // Load the new user from the database.
+    let user: User = schema::users::table.find(&id).select(User::as_select()).first(&mut connection).unwrap();
+
+    // Decrypt the user's secrets.
+    assert_eq!(user.json.as_ref().unwrap().decrypt().unwrap(), "Very secret.");
+
+    // Update the user's secrets.
+    diesel::update(schema::users::table.find(&id))
+        .set(UserChangeset {
+            json: Some(Some(EncryptedMessage::encrypt("Even more secret.".to_string()).unwrap())),
+        })
+        .execute(&mut connection)
+        .unwrap();
+
+    let user: User = schema::users::table.find(&id).select(User::as_select()).first(&mut connection).unwrap();
+    assert_eq!(user.json.as_ref().unwrap().decrypt().unwrap(), "Even more secret.");
+}
+
+#[cfg(feature = "diesel-binary")]
+#[test]
+fn binary_encrypted_message_works() {
+    let mut connection = SqliteConnection::establish(":memory:").unwrap();
+    diesel::sql_query("CREATE TABLE users (id TEXT PRIMARY KEY NOT NULL, json TEXT, binary BLOB)")
+        .execute(&mut connection)
+        .unwrap();
+
+    // Create a new user, with a secret stored in its binary representation.
+    let id = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(schema::users::table)
+        .values((
+            schema::users::id.eq(&id),
+            schema::users::binary.eq(Some(EncryptedMessage::<String, EncryptionConfig>::encrypt("Very secret, & compact.".to_string()).unwrap())),
+        ))
+        .execute(&mut connection)
+        .unwrap();
+
+    // Load & decrypt the user's secret.
+    let secret: Option<EncryptedMessage<String, EncryptionConfig>> = schema::users::table.find(&id).select(schema::users::binary).first(&mut connection).unwrap();
+    assert_eq!(secret.unwrap().decrypt().unwrap(), "Very secret, & compact.");
+}
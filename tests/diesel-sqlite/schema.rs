@@ -0,0 +1,7 @@
+diesel::table! {
+    users (id) {
+        id -> Text,
+        json -> Nullable<Text>,
+        binary -> Nullable<Binary>,
+    }
+}
@@ -0,0 +1,86 @@
+//! Tests that no key bytes are left in freed heap memory, using an allocator inspecting the blocks it deallocates.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use encrypted_message::{
+    EncryptedMessage,
+    config::{Config, Secret},
+    keyring::Keyring,
+    strategy::Randomized,
+};
+
+/// The keys of the keyring, which must never be found in freed memory.
+const KEYS: [[u8; 32]; 2] = {
+    let mut keys = [[0; 32]; 2];
+    let mut i = 0;
+    while i < 32 {
+        keys[0][i] = 0xa0 + i as u8;
+        keys[1][i] = 0xc0 + i as u8;
+        i += 1;
+    }
+
+    keys
+};
+
+const KEYRING: &str = r#"{
+    "primary": "a",
+    "keys": {
+        "a": "a0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babbbcbdbebf",
+        "b": "wMHCw8TFxsfIycrLzM3Oz9DR0tPU1dbX2Nna29zd3t8="
+    }
+}"#;
+
+/// The number of freed blocks that contained a key.
+static LEAKS: AtomicUsize = AtomicUsize::new(0);
+
+struct InspectingAllocator;
+
+unsafe impl GlobalAlloc for InspectingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { System.alloc(layout) }
+    }
+
+    // `realloc` isn't overridden, so reallocated blocks are inspected too.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let block = unsafe { std::slice::from_raw_parts(ptr, layout.size()) };
+        if KEYS.iter().any(|key| block.windows(key.len()).any(|window| window == key)) {
+            LEAKS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: InspectingAllocator = InspectingAllocator;
+
+#[derive(Debug)]
+struct KeyringConfig(Keyring);
+
+impl Config for KeyringConfig {
+    type Strategy = Randomized;
+
+    fn keys(&self) -> Vec<Secret<[u8; 32]>> {
+        self.0.keys()
+    }
+}
+
+#[test]
+fn keys_are_zeroized() {
+    {
+        let config = KeyringConfig(Keyring::from_json(KEYRING).unwrap());
+
+        let message = EncryptedMessage::<String, KeyringConfig>::encrypt_with_config("rigo does pretty codes".to_string(), &config).unwrap();
+        assert_eq!(message.decrypt_with_config(&config).unwrap(), "rigo does pretty codes");
+
+        let message = EncryptedMessage::<String, KeyringConfig>::encrypt_with_config_using_key("rigo does pretty codes".to_string(), &config, 1).unwrap();
+        assert_eq!(message.decrypt_with_config(&config).unwrap(), "rigo does pretty codes");
+
+        EncryptedMessage::<String, KeyringConfig>::blind_index_with_config("rigo does pretty codes", &config).unwrap();
+    }
+
+    assert_eq!(LEAKS.load(Ordering::SeqCst), 0);
+}